@@ -194,6 +194,16 @@ zombie_type_widen:
 	
 	ret
 
+stat_mul: #the same conversion as mul_from_u8, from r8d into xmm0
+	shlb     $1,    %r8b
+	cvtsi2ss %r8d,  %xmm0
+	mulss    stat_mul.constA(%rip), %xmm0
+	addss    stat_mul.constB(%rip), %xmm0
+	jc       stat_mul.locA
+		mulss stat_mul.constC(%rip), %xmm0
+	stat_mul.locA:
+	ret
+
 init_hash_table_u32_u32: #log2(table_size) in cl, table in rdx, array in r8, init array in r9, array length in r10
 	pushq %rdi
 	pushq %rsi
@@ -478,6 +488,13 @@ card_create_label: #seed packet cost in ecx
 exit_seed_select:
 	movb $0, on_seed_select(%rip)
 	movq %rbx, 0x10(%rsp)
+	movq set_wave_count_ptr(%rip), %rax
+	testq %rax, %rax
+	je   exit_seed_select.locA
+		pushq %rcx
+		call  *%rax
+		popq  %rcx
+	exit_seed_select.locA:
 	ret
 
 wait_on_rust:
@@ -510,6 +527,14 @@ store_mix_data_ptr:
 	ret
 
 .section .data
+stat_mul.constA:
+	.float 0.00393700787402
+stat_mul.constB:
+	.float 1.0
+stat_mul.constC:
+	.float 0.5
+max_plant_type: #the highest PlantType, written from the game's enum after patching
+	.long 1227
 card_create_label.constA:
 	.float 0.0000001
 	.float 0.000001
@@ -538,6 +563,8 @@ fetch_cooldown_ptr:
 	.quad "OR_NULL fetch_cooldown"
 fetch_firerate_ptr:
 	.quad "OR_NULL fetch_firerate"
+set_wave_count_ptr:
+	.quad "OR_NULL set_wave_count"
 game_app_ptr:
 	.quad 0
 mix_data_ptr:
@@ -0,0 +1,44 @@
+.section .text
+
+"ConveyManager::GetRandomPlant(&mut self) -> PlantType":
+	jmp pick_conveyor_plant
+"ENDConveyManager::GetRandomPlant(&mut self) -> PlantType":
+
+pick_conveyor_plant:
+	leaq conveyor_weights(%rip), %r8
+	xorl %r9d, %r9d
+	xorl %eax, %eax
+	pick_conveyor_plant.loopA:
+		movzbl (%r8,%rax), %r10d
+		addl   %r10d,      %r9d
+		incl   %eax
+		cmpl   $48,        %eax
+	jne pick_conveyor_plant.loopA
+	testl %r9d, %r9d
+	jne   pick_conveyor_plant.locA
+	jmp   "ConveyManager::GetRandomPlant(&mut self) -> PlantType.original" #no weights for this level
+	pick_conveyor_plant.locA:
+	
+	subq $0x28, %rsp
+	
+	movl %r9d, %edx
+	xorl %ecx, %ecx
+	call "UnityEngine::Random::RandomRangeInt(minInclusive: i32, maxExclusive: i32) -> i32"
+	
+	leaq conveyor_weights(%rip), %r8
+	movq $-1, %rcx
+	pick_conveyor_plant.loopB:
+		incq   %rcx
+		movzbl (%r8,%rcx), %edx
+		subl   %edx,       %eax
+	jnc pick_conveyor_plant.loopB
+	
+	leaq menu_init_array(%rip), %rdx
+	movl (%rdx,%rcx,8),         %eax
+	
+	addq $0x28, %rsp
+	ret
+
+.section .data
+conveyor_weights: #weight of each menu plant, written before every level and all zero when the level keeps its own conveyor
+	.space 48
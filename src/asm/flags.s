@@ -0,0 +1,18 @@
+.section .text
+
+set_wave_count: #called from exit_seed_select, so it runs once the board for the level exists
+	movzbl flag_count(%rip), %edx
+	testl  %edx, %edx
+	je     set_wave_count.locA
+	movq   game_app_ptr(%rip),  %rax
+	movq   GameAPP.board(%rax), %rax
+	testq  %rax, %rax
+	je     set_wave_count.locA
+		imull $10, %edx
+		movl  %edx, Board.theMaxWave(%rax)
+	set_wave_count.locA:
+	ret
+
+.section .data
+flag_count: #flags in the current level, written before every level, 0 leaves the level's own wave count
+	.byte 0
@@ -0,0 +1,45 @@
+.section .text
+
+"Plant::Start(&mut self)":
+	jmp scale_plant_stats
+"ENDPlant::Start(&mut self)":
+
+scale_plant_stats: #runs after Plant::Start, which sets the stats this scales
+	pushq %rcx
+	subq  $0x20, %rsp
+	call  "Plant::Start(&mut self).original" #rdx is passed through untouched
+	addq  $0x20, %rsp
+	movq  (%rsp), %rcx
+	movl  Plant.thePlantType(%rcx), %ecx
+	cmpl  max_plant_type(%rip), %ecx
+	ja    scale_plant_stats.locA
+	call  plant_type_flatten
+	popq  %rcx
+	cmpq  $384, %rax
+	jnc   scale_plant_stats.locB
+		leaq plant_damage_table(%rip), %rdx
+		movzbl   (%rdx,%rax), %r8d
+		call     stat_mul
+		cvtsi2ss Plant.attackDamage(%rcx), %xmm1
+		mulss    %xmm0,       %xmm1
+		cvtss2si %xmm1,       %edx
+		movl     %edx, Plant.attackDamage(%rcx)
+		
+		leaq plant_sun_table(%rip), %rdx
+		movzbl   (%rdx,%rax), %r8d
+		call     stat_mul
+		cvtsi2ss Plant.produceSunCount(%rcx), %xmm1
+		mulss    %xmm0,       %xmm1
+		cvtss2si %xmm1,       %edx
+		movl     %edx, Plant.produceSunCount(%rcx)
+	jmp scale_plant_stats.locB
+	scale_plant_stats.locA:
+	popq %rcx
+	scale_plant_stats.locB:
+	ret
+
+.section .data
+plant_damage_table: #damage multiplier of each plant, written before every level
+	.space 384, 0x80
+plant_sun_table: #sun production multiplier of each plant, written before every level
+	.space 384, 0x80
@@ -0,0 +1,30 @@
+.section .text
+
+"Board::Start(&mut self)":
+	jmp set_scene_type
+"ENDBoard::Start(&mut self)":
+
+set_scene_type: #runs before Board::Start, which sets the board up for its scene
+	movslq scene_type(%rip), %rax
+	testq  %rax, %rax
+	js     set_scene_type.locA
+	movq   game_app_ptr(%rip), %r8
+	testq  %r8, %r8
+	je     set_scene_type.locA
+	cmpl   $"LevelType::Advanture", GameAPP.theBoardType(%r8)
+	jne    set_scene_type.locA
+		leaq scene_types(%rip), %r8
+		movl (%r8,%rax,4),      %eax
+		movl %eax, Board.theSceneType(%rcx)
+	set_scene_type.locA:
+	jmp "Board::Start(&mut self).original"
+
+.section .data
+scene_type: #LevelType of the current level, written before every level, -1 leaves the level's own scene
+	.long -1
+scene_types: #SceneType of each LevelType
+	.long "SceneType::Day"
+	.long "SceneType::Night"
+	.long "SceneType::Pool"
+	.long "SceneType::Fog"
+	.long "SceneType::Roof"
@@ -0,0 +1,45 @@
+.section .text
+
+"Zombie::Start(&mut self)":
+	jmp scale_zombie_stats
+"ENDZombie::Start(&mut self)":
+
+scale_zombie_stats: #runs after Zombie::Start, which sets the stats this scales
+	pushq %rcx
+	subq  $0x20, %rsp
+	call  "Zombie::Start(&mut self).original" #rdx is passed through untouched
+	addq  $0x20, %rsp
+	movq  (%rsp), %rcx
+	movl  Zombie.theZombieType(%rcx), %ecx
+	call  zombie_type_flatten
+	popq  %rcx
+	cmpq  $128, %rax #also skips the negative types zombie_type_flatten can't place
+	jnc   scale_zombie_stats.locA
+		leaq zombie_stat_table(%rip), %rdx
+		addq %rax,                    %rdx
+		
+		movzbl   (%rdx),  %r8d
+		call     stat_mul
+		cvtsi2ss Zombie.theHealth(%rcx), %xmm1
+		mulss    %xmm0,   %xmm1
+		cvtss2si %xmm1,   %eax
+		movl     %eax, Zombie.theHealth(%rcx)
+		movl     %eax, Zombie.theMaxHealth(%rcx)
+		
+		movzbl 128(%rdx), %r8d
+		call   stat_mul
+		mulss  Zombie.theSpeed(%rcx), %xmm0
+		movss  %xmm0, Zombie.theSpeed(%rcx)
+		
+		movzbl   256(%rdx), %r8d
+		call     stat_mul
+		cvtsi2ss Zombie.theAttackDamage(%rcx), %xmm1
+		mulss    %xmm0,     %xmm1
+		cvtss2si %xmm1,     %eax
+		movl     %eax, Zombie.theAttackDamage(%rcx)
+	scale_zombie_stats.locA:
+	ret
+
+.section .data
+zombie_stat_table: #health, speed then damage multiplier of each zombie, written before every level
+	.space 384, 0x80
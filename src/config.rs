@@ -0,0 +1,107 @@
+use std::{env, error::Error, fs::{create_dir_all, read_to_string, OpenOptions}, io::Write, path::PathBuf, time::{SystemTime, UNIX_EPOCH}};
+
+use serde::{Deserialize, Serialize};
+
+use crate::settings::Cfg;
+
+const MAX_RECENT_SEEDS: usize = 10;
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub cfg:          Option<Cfg>,
+    pub recent_seeds: Vec<RecentSeed>,
+    pub dump_dir:     Option<PathBuf>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RecentSeed {
+    pub cfg:       Cfg,
+    pub timestamp: u64, //seconds since the unix epoch
+}
+
+impl RecentSeed {
+    pub fn label(&self) -> String {
+        let days    = (self.timestamp / 86400) as i64;
+        let seconds = self.timestamp % 86400;
+        
+        //days to civil date, from http://howardhinnant.github.io/date_algorithms.html
+        let z   = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp  = (5 * doy + 2) / 153;
+        let day   = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 {mp + 3} else {mp - 9};
+        let year  = yoe + era * 400 + (month <= 2) as i64;
+        
+        format!("{} ({year}-{month:02}-{day:02} {:02}:{:02} UTC)", self.cfg.seed, seconds / 3600, seconds / 60 % 60)
+    }
+}
+
+pub fn config_dir() -> Option<PathBuf> {
+    let dir = if cfg!(target_os = "windows") {
+        PathBuf::from(env::var_os("APPDATA")?)
+    } else if let Some(dir) = env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        PathBuf::from(dir)
+    } else {
+        PathBuf::from(env::var_os("HOME")?).join(".config")
+    };
+    
+    Some(dir.join("fusion-randomiser"))
+}
+
+fn config_path() -> Option<PathBuf> {
+    Some(config_dir()?.join("config.json"))
+}
+
+impl Config {
+    pub fn load() -> Self {
+        let Some(path) = config_path() else {
+            return Self::default();
+        };
+        let Ok(text) = read_to_string(&path) else {
+            return Self::default();
+        };
+        
+        match serde_json::from_str(&text) {
+            Ok(config) => config,
+            Err(err) => {
+                println!("Failed to read config from {}: {err}", path.display());
+                Self::default()
+            }
+        }
+    }
+    
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let Some(path) = config_path() else {
+            return Ok(());
+        };
+        create_dir_all(path.parent().unwrap())?;
+        
+        let mut out_file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(path)?;
+        
+        out_file.write_all(serde_json::to_string_pretty(self)?.as_bytes())?;
+        
+        Ok(())
+    }
+    
+    pub fn add_recent_seed(&mut self, cfg: &Cfg) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or(0);
+        
+        self.recent_seeds.retain(|recent| recent.cfg.seed != cfg.seed);
+        self.recent_seeds.insert(0, RecentSeed {
+            cfg: cfg.clone(),
+            timestamp,
+        });
+        self.recent_seeds.truncate(MAX_RECENT_SEEDS);
+    }
+}
@@ -1,9 +1,8 @@
-use std::{collections::HashMap, hash::BuildHasherDefault, sync::OnceLock};
+use std::{collections::HashMap, mem::transmute, sync::OnceLock};
 
 use bitflags::bitflags;
 use fxhash::{FxHashMap, FxHashSet};
-
-use crate::il2cppdump::IL2CppDumper;
+use serde::{Deserialize, Serialize};
 
 pub static ZOMBIE_DATA: OnceLock<Vec<ZombieData>> = OnceLock::new();
 pub static LEVEL_DATA:  OnceLock<Vec<LevelData>>  = OnceLock::new();
@@ -44,7 +43,7 @@ bitflags! {
     }
 }
 
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum LevelType {
     Day,
     Night,
@@ -53,7 +52,29 @@ pub enum LevelType {
     Roof,
 }
 
-#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+impl LevelType {
+    pub const ALL: [Self; 5] = [
+        Self::Day,
+        Self::Night,
+        Self::Pool,
+        Self::Fog,
+        Self::Roof,
+    ];
+    
+    pub fn from_u8(val: u8) -> Option<Self> {
+        Self::ALL.get(val as usize).copied()
+    }
+    
+    pub fn has_water(self) -> bool {
+        matches!(self, Self::Pool | Self::Fog)
+    }
+    
+    pub fn is_night(self) -> bool {
+        matches!(self, Self::Night | Self::Fog)
+    }
+}
+
+#[derive(Debug, Deserialize, Hash, PartialEq, Eq, Clone, Copy, Serialize)]
 pub enum ZombieType {
     NormalZombie,
     ConeZombie,
@@ -162,7 +183,7 @@ pub enum ZombieType {
 }
 
 #[allow(dead_code)]
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub enum Unlockable {
     Peashooter = 0,
     SunFlower,
@@ -207,6 +228,20 @@ pub enum Unlockable {
     CobCannon,
 }
 
+impl Unlockable {
+    pub fn from_u8(val: u8) -> Option<Self> {
+        if val <= Self::CobCannon as u8 {
+            Some(unsafe { transmute::<u8, Self>(val) })
+        } else {
+            None
+        }
+    }
+    
+    pub fn from_name(name: &str) -> Option<Self> {
+        (0..=Self::CobCannon as u8).filter_map(Self::from_u8).find(|plant| format!("{plant:?}").eq_ignore_ascii_case(name))
+    }
+}
+
 pub struct LevelData {
     pub level_type: LevelType,
     pub flags: Option<u8>,
@@ -250,14 +285,67 @@ pub const COOLDOWN_TABLE: [f32; 41] = [
     50., 50., 15., 50., 50., 50., 50., 50.,
 ];
 
-pub fn init_defaults_from_dump(dump: &IL2CppDumper) {
-    let mut il2cpp_syms: FxHashMap<String, u64> = HashMap::with_capacity_and_hasher(dump.methods_array.len()*3, BuildHasherDefault::default());
-    
-    dump.get_field_offsets(&mut il2cpp_syms);
-    dump.get_enum_variants(&mut il2cpp_syms);
-    
-    il2cpp_syms.shrink_to_fit();
-    
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlantRole {
+    Attacker,
+    Defender,
+    Economy,
+    Instant,
+    Support,
+}
+
+pub struct PlantData {
+    pub role:       PlantRole,
+    pub cost:       u16,
+    pub usefulness: f32, //default weight for weighted plant picks, rules.ron can override it
+}
+
+const fn plant(role: PlantRole, cost: u16, usefulness: f32) -> PlantData {
+    PlantData {role, cost, usefulness}
+}
+
+pub const PLANT_DATA: [PlantData; 41] = {
+    use PlantRole::*;
+    [
+        plant(Attacker, 100, 5.), plant(Economy,   50, 8.), plant(Instant, 150, 6.), plant(Defender,  50, 7.),
+        plant(Instant,   25, 4.), plant(Attacker, 150, 4.), plant(Attacker,  0, 5.), plant(Attacker,  75, 7.),
+        plant(Support,   75, 3.), plant(Attacker,  25, 4.), plant(Instant,   75, 5.), plant(Instant,  125, 5.),
+        plant(Support,   25, 8.), plant(Instant,   50, 6.), plant(Attacker, 325, 7.), plant(Instant,   25, 4.),
+        plant(Instant,  125, 5.), plant(Support,  100, 4.), plant(Support,  175, 6.), plant(Attacker,   0, 4.),
+        plant(Support,   25, 3.), plant(Attacker, 125, 4.), plant(Support,  100, 3.), plant(Attacker, 125, 6.),
+        plant(Defender, 125, 6.), plant(Support,  100, 4.), plant(Attacker, 100, 5.), plant(Support,   25, 8.),
+        plant(Attacker, 100, 5.), plant(Support,   50, 3.), plant(Support,  100, 3.), plant(Economy,   50, 2.),
+        plant(Attacker, 300, 7.), plant(Support,  100, 2.), plant(Attacker, 150, 6.), plant(Support,   50, 3.),
+        plant(Defender, 125, 7.), plant(Support,  125, 5.), plant(Attacker, 225, 6.), plant(Attacker, 150, 7.),
+        plant(Attacker, 500, 6.),
+    ]
+};
+
+pub fn level_label(level: usize) -> String { //level is 1 indexed
+    level_label_in_scene(level, LEVEL_DATA.get().unwrap()[level - 1].level_type)
+}
+
+pub fn level_label_in_scene(level: usize, scene: LevelType) -> String { //the world comes from the scene, the stage from the level's own position
+    let level_data = LEVEL_DATA.get().unwrap();
+    let level_type = level_data[level - 1].level_type;
+    let world = match scene {
+        LevelType::Day => 1,
+        LevelType::Night => 2,
+        LevelType::Pool => 3,
+        LevelType::Fog => 4,
+        LevelType::Roof => 5,
+    };
+    let mut stage = 1;
+    for level in level_data[0 .. level - 1].iter().rev() {
+        if level.level_type != level_type {
+            break;
+        }
+        stage += 1;
+    }
+    format!("{world}-{stage}")
+}
+
+pub fn init_defaults(enum_variants: &FxHashMap<String, u64>) {
     let mut level_array = vec![
         LevelData { //1
             ..Default::default()
@@ -1638,7 +1726,7 @@ pub fn init_defaults_from_dump(dump: &IL2CppDumper) {
     ];
     
     for zombie in zombie_array.iter_mut() {
-        zombie.id = match il2cpp_syms.get(&format!("ZombieType::{:?}", zombie.zombie_type)) {
+        zombie.id = match enum_variants.get(&format!("ZombieType::{:?}", zombie.zombie_type)) {
             Some(x) => {
                 Some(*x as i32)
             },
@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Difficulty {
+    Casual,
+    #[default]
+    Standard,
+    Hard,
+    Chaos,
+}
+
+pub struct DifficultyParams {
+    pub odyssey_levels: usize,    //odyssey zombies only spawn in the last this many levels
+    pub weight_spread:  f64,      //zombie weights are multiplied by between 10^-spread and 10^spread
+    pub mul_range:      (u8, u8), //range of the bytes read by mul_from_u8, 0x00..=0xFF is 0.5x to 2x
+}
+
+impl Difficulty {
+    pub const ALL: [Self; 4] = [
+        Self::Casual,
+        Self::Standard,
+        Self::Hard,
+        Self::Chaos,
+    ];
+    
+    pub fn params(self) -> DifficultyParams {
+        match self {
+            Self::Casual => DifficultyParams {
+                odyssey_levels: 10,
+                weight_spread:  0.5,
+                mul_range:      (0x40, 0xC0),
+            },
+            Self::Standard => DifficultyParams {
+                odyssey_levels: 15,
+                weight_spread:  1.,
+                mul_range:      (0x00, 0xFF),
+            },
+            Self::Hard => DifficultyParams {
+                odyssey_levels: 20,
+                weight_spread:  1.,
+                mul_range:      (0x00, 0xFF),
+            },
+            Self::Chaos => DifficultyParams {
+                odyssey_levels: 25,
+                weight_spread:  1.5,
+                mul_range:      (0x00, 0xFF),
+            },
+        }
+    }
+    
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Casual => "Odyssey zombies only show up in the last 10 levels, zombie weights stay between 0.3x and 3x and plant stats stay between 0.75x and 1.5x.",
+            Self::Standard => "Odyssey zombies show up in the last 15 levels, zombie weights are between 0.1x and 10x and plant stats are between 0.5x and 2x.",
+            Self::Hard => "Like standard, but odyssey zombies show up in the last 20 levels.",
+            Self::Chaos => "Odyssey zombies show up in the last 25 levels and zombie weights are between 0.03x and 30x.",
+        }
+    }
+    
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|difficulty| format!("{difficulty:?}").eq_ignore_ascii_case(name))
+    }
+    
+    pub fn from_u8(val: u8) -> Option<Self> {
+        Self::ALL.get(val as usize).copied()
+    }
+}
+
+impl DifficultyParams {
+    pub fn scale_mul(&self, byte: u8) -> u8 { //maps a full range random byte into mul_range
+        let (low, high) = self.mul_range;
+        low + ((byte as u32 * (high - low) as u32 + 127) / 255) as u8
+    }
+}
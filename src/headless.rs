@@ -0,0 +1,514 @@
+use std::{collections::HashMap, error::Error, fs::{read_to_string, OpenOptions}, io::Write, mem::transmute, num::NonZero, panic::{catch_unwind, set_hook, take_hook, AssertUnwindSafe}, path::{Path, PathBuf}, sync::{atomic::{AtomicU64, Ordering}, Mutex}, thread::{self, available_parallelism}};
+
+use fxhash::FxHashMap;
+
+use crate::{format_to, data::{init_defaults, level_label_in_scene, Unlockable}, difficulty::Difficulty, il2cppdump::IL2CppDumper, logic::{GenerationOptions, GenerationStats, RandomisationData}, plan::Plan, plant_options::PlantOptions, rules::{init_rules, Rules, DEFAULT_RULES}, settings::Cfg, spoiler::Spoiler, util::{hash_str, CommonError}};
+
+const USAGE: &str = "Usage:
+    fusion-randomiser generate <game dir> <fuse map> <seed> [--no-restrictions] [--random-conveyors] [--random-flags] [--random-scenes] [--random-zombie-stats] [--random-damage] [--random-sun] [--random-fusions] [--progressive-weights] [--difficulty <difficulty>] [<plant options>] [--reroll <slot>]... [--plan <plan file>] [--spoiler <out dir>]
+    fusion-randomiser generate <game dir> <fuse map> --settings <settings code> [--reroll <slot>]... [--plan <plan file>] [--spoiler <out dir>]
+    fusion-randomiser validate <game dir> <fuse map> [--count <n>] [--start <seed>] [--threads <n>] [--difficulty <difficulty>] [<plant options>]
+    fusion-randomiser rules check <rules file>
+    fusion-randomiser rules export <out file>
+
+<game dir> is the directory containing GameAssembly.dll.
+<fuse map> is the fuse_map.txt written when dumping from a running game.
+--settings uses the seed and options from a settings code copied from the options screen, so the other options can't be given with it.
+--spoiler writes spoiler.txt and spoiler.json to <out dir>.
+--random-conveyors randomises the plants and their frequencies on conveyor levels.
+--random-flags randomises how many flags each level has.
+--random-scenes gives each level a random day, night, pool, fog or roof scene.
+--random-zombie-stats randomises the health, speed and damage of zombies in each level.
+--random-damage and --random-sun randomise plant damage and sun production in each level.
+--random-fusions shuffles which two plants fuse into each fusion.
+--progressive-weights makes zombies other than the basic one, and elites even more so, rarer in early slots and more common in late ones.
+--reroll gives the level played in <slot> (2 to 45) new zombies and plant stats, keeping everything else. It can be repeated to reroll a slot again. A reroll that gives an impossible level is rerolled again, and the output lists the reroll counts used.
+--plan fixes the levels, unlocks, zombies and plant stats listed in <plan file> and randomises the rest around them. It needs restrictions.
+--difficulty is one of casual, standard (the default), hard or chaos.
+<plant options> only apply with restrictions:
+    --first-plant <plant> pins the plant unlocked by level 1.
+    --early <plant,...> unlocks these plants within the first --early-levels <n> levels (1 to 45, default 10).
+    --exclude <plant,...> never unlocks these plants.
+    --weighted-plants unlocks more useful plants earlier instead of picking the rest uniformly.
+validate generates the numeric seeds <seed>..<seed>+<n> with restrictions (default 0..1000) and reports on each of them.
+rules check reports any problems with a rules file, rules export writes the built in rules as a starting point.
+Custom rules are read from rules.ron in the config directory, next to config.json. Settings codes only work with the rules they were made with.";
+
+const COMMANDS: [&str; 6] = ["generate", "validate", "rules", "help", "--help", "-h"];
+
+pub fn wants_headless(args: &[String]) -> bool { //anything else, like arguments added by a launcher, still opens the window
+    args.get(1).is_some_and(|arg| COMMANDS.contains(&arg.as_str()))
+}
+
+pub fn run(args: &[String]) -> Result<(), Box<dyn Error>> {
+    match args.get(1).map(|arg| arg.as_str()) {
+        Some("generate") => {
+            init_rules()?;
+            generate(&args[2..])
+        }
+        Some("validate") => {
+            init_rules()?;
+            validate(&args[2..])
+        }
+        Some("rules") => rules(&args[2..]),
+        Some("help" | "--help" | "-h") => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        _ => Err(Box::new(CommonError::critical(&format!("Unknown command\n{USAGE}")))),
+    }
+}
+
+fn generate(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut positional: Vec<&String> = Vec::new();
+    let mut restrictions = true;
+    let mut conveyors = false;
+    let mut flag_counts = false;
+    let mut scenes = false;
+    let mut zombie_stats = false;
+    let mut damage = false;
+    let mut sun = false;
+    let mut fusions = false;
+    let mut progressive_weights = false;
+    let mut rerolls: Vec<u8> = Vec::new();
+    let mut plan: Option<Plan> = None;
+    let mut settings: Option<Cfg> = None;
+    let mut spoiler_dir: Option<PathBuf> = None;
+    let mut difficulty = Difficulty::Standard;
+    let mut plant_options = PlantOptions::default();
+    let mut option_arg: Option<&String> = None; //the first option a settings code would override
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if parse_plant_option(arg, &mut args, &mut plant_options)? {
+            option_arg.get_or_insert(arg);
+            continue;
+        }
+        if matches!(arg.as_str(), "--no-restrictions" | "--random-conveyors" | "--random-flags" | "--random-scenes" | "--random-zombie-stats" | "--random-damage" | "--random-sun" | "--random-fusions" | "--progressive-weights" | "--difficulty") {
+            option_arg.get_or_insert(arg);
+        }
+        match arg.as_str() {
+            "--no-restrictions" => restrictions = false,
+            "--random-conveyors" => conveyors = true,
+            "--random-flags" => flag_counts = true,
+            "--random-scenes" => scenes = true,
+            "--random-zombie-stats" => zombie_stats = true,
+            "--random-damage" => damage = true,
+            "--random-sun" => sun = true,
+            "--random-fusions" => fusions = true,
+            "--progressive-weights" => progressive_weights = true,
+            "--reroll" => match args.next().and_then(|slot| slot.parse().ok()) {
+                Some(slot) => rerolls.push(slot),
+                None => return Err(Box::new(CommonError::critical(&format!("--reroll needs a slot number\n{USAGE}")))),
+            },
+            "--plan" => match args.next() {
+                Some(path) => plan = Some(Plan::load_file(path)?),
+                None => return Err(Box::new(CommonError::critical(&format!("--plan needs a plan file\n{USAGE}")))),
+            },
+            "--settings" => match args.next() {
+                Some(code) => settings = Some(Cfg::from_settings_string(code)?),
+                None => return Err(Box::new(CommonError::critical(&format!("--settings needs a settings code\n{USAGE}")))),
+            },
+            "--spoiler" => match args.next() {
+                Some(dir) => spoiler_dir = Some(PathBuf::from(dir)),
+                None => return Err(Box::new(CommonError::critical(&format!("--spoiler needs a directory\n{USAGE}")))),
+            },
+            "--difficulty" => difficulty = parse_difficulty(args.next())?,
+            _ => positional.push(arg),
+        }
+    }
+    let (game_dir, fuse_map_path, mut cfg) = match (&positional[..], settings) {
+        ([game_dir, fuse_map_path], Some(cfg)) => match option_arg {
+            Some(arg) => return Err(Box::new(CommonError::critical(&format!("{arg} can't be used with --settings, the settings code already sets it\n{USAGE}")))),
+            None => (game_dir, fuse_map_path, cfg),
+        },
+        ([game_dir, fuse_map_path, seed], None) => (game_dir, fuse_map_path, Cfg {
+            firerates_enabled: true,
+            costs_enabled:     true,
+            cooldowns_enabled: true,
+            spawns_enabled:    true,
+            tweaks_enabled:    true,
+            restrictions,
+            sounds:            false,
+            conveyors_enabled: conveyors,
+            flag_counts_enabled: flag_counts,
+            scenes_enabled: scenes,
+            zombie_stats_enabled: zombie_stats,
+            damage_enabled: damage,
+            sun_enabled: sun,
+            fusions_enabled: fusions,
+            progressive_weights_enabled: progressive_weights,
+            seed:   seed.to_string(),
+            sound_chance:        0.0,
+            difficulty,
+            plant_options,
+            rerolls: Vec::new(),
+            plan: None,
+        }),
+        _ => return Err(Box::new(CommonError::critical(&format!("Wrong number of arguments\n{USAGE}")))),
+    };
+    
+    for slot in rerolls {
+        cfg.reroll(slot);
+    }
+    if plan.is_some() {
+        if !cfg.restrictions {
+            return Err(Box::new(CommonError::critical(&format!("--plan needs restrictions\n{USAGE}"))));
+        }
+        cfg.plan = plan;
+    }
+    
+    let GameData {enum_variants, fuse_map} = load_game_data(game_dir, fuse_map_path)?;
+    
+    let rand_data = cfg.randomise(&enum_variants, &fuse_map)?;
+    
+    print!("{}", describe(&rand_data));
+    
+    if let Some(spoiler_dir) = spoiler_dir {
+        Spoiler::new(&cfg.seed, &rand_data, &enum_variants).output(spoiler_dir)?;
+    }
+    
+    Ok(())
+}
+
+fn parse_difficulty(name: Option<&String>) -> Result<Difficulty, Box<dyn Error>> {
+    match name.and_then(|name| Difficulty::from_name(name)) {
+        Some(difficulty) => Ok(difficulty),
+        None => Err(Box::new(CommonError::critical(&format!("--difficulty needs one of casual, standard, hard or chaos\n{USAGE}")))),
+    }
+}
+
+fn parse_plants(list: Option<&String>, arg: &str) -> Result<Vec<Unlockable>, Box<dyn Error>> {
+    let Some(list) = list else {
+        return Err(Box::new(CommonError::critical(&format!("{arg} needs a comma separated list of plants\n{USAGE}"))));
+    };
+    let mut ret = Vec::new();
+    for name in list.split(',').filter(|name| !name.is_empty()) {
+        match Unlockable::from_name(name.trim()) {
+            Some(plant) => ret.push(plant),
+            None => return Err(Box::new(CommonError::critical(&format!("{name} is not a plant")))),
+        }
+    }
+    Ok(ret)
+}
+
+fn parse_plant_option<'a, T: Iterator<Item = &'a String>>(arg: &str, args: &mut T, plant_options: &mut PlantOptions) -> Result<bool, Box<dyn Error>> {
+    match arg {
+        "--first-plant" => match parse_plants(args.next(), arg)?[..] {
+            [plant] => plant_options.first_plant = Some(plant),
+            _ => return Err(Box::new(CommonError::critical(&format!("--first-plant needs exactly one plant\n{USAGE}")))),
+        },
+        "--early" => plant_options.early_plants = parse_plants(args.next(), arg)?,
+        "--exclude" => plant_options.excluded = parse_plants(args.next(), arg)?,
+        "--weighted-plants" => plant_options.weighted = true,
+        "--early-levels" => match args.next().map(|levels| levels.parse()) {
+            Some(Ok(levels @ 1..=45)) => plant_options.early_levels = levels,
+            _ => return Err(Box::new(CommonError::critical(&format!("--early-levels needs a number of levels from 1 to 45\n{USAGE}")))),
+        },
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+struct GameData {
+    enum_variants: FxHashMap<String, u64>,
+    fuse_map:      FxHashMap<u32,[u32;2]>,
+}
+
+fn load_game_data(game_dir: &str, fuse_map_path: &str) -> Result<GameData, Box<dyn Error>> {
+    let dumper = IL2CppDumper::initialize(&PathBuf::from(game_dir))
+        .map_err(|err| CommonError::critical(&err))?;
+    let enum_variants = dumper.enum_variants();
+    init_defaults(&enum_variants);
+    let fuse_map = read_fuse_map(fuse_map_path)?;
+    
+    Ok(GameData {
+        enum_variants,
+        fuse_map,
+    })
+}
+
+enum SeedResult {
+    Generated(GenerationStats),
+    Failed(String),   //the generator gave up on the seed
+    Panicked(String), //the generator hit a bug
+}
+
+fn validate(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut positional: Vec<&String> = Vec::new();
+    let mut count = 1000u64;
+    let mut start = 0u64;
+    let mut threads = available_parallelism().unwrap_or(NonZero::new(1).unwrap()).get();
+    let mut difficulty = Difficulty::Standard;
+    let mut plant_options = PlantOptions::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if parse_plant_option(arg, &mut args, &mut plant_options)? {
+            continue;
+        }
+        let target = match arg.as_str() {
+            "--count"   => &mut count,
+            "--start"   => &mut start,
+            "--threads" => {
+                threads = match args.next().and_then(|num| num.parse().ok()) {
+                    Some(num) if num > 0 => num,
+                    _ => return Err(Box::new(CommonError::critical(&format!("--threads needs a positive number\n{USAGE}")))),
+                };
+                continue;
+            }
+            "--difficulty" => {
+                difficulty = parse_difficulty(args.next())?;
+                continue;
+            }
+            _ => {
+                positional.push(arg);
+                continue;
+            }
+        };
+        *target = match args.next().and_then(|num| num.parse().ok()) {
+            Some(num) => num,
+            None => return Err(Box::new(CommonError::critical(&format!("{arg} needs a number\n{USAGE}")))),
+        };
+    }
+    let [game_dir, fuse_map_path] = positional[..] else {
+        return Err(Box::new(CommonError::critical(&format!("Wrong number of arguments\n{USAGE}"))));
+    };
+    
+    let GameData {enum_variants, fuse_map} = load_game_data(game_dir, fuse_map_path)?;
+    
+    let next_seed = AtomicU64::new(start);
+    let results: Mutex<Vec<(u64, SeedResult)>> = Mutex::new(Vec::with_capacity(count as usize));
+    
+    let default_hook = take_hook();
+    set_hook(Box::new(|_| {})); //panics are reported in the summary instead
+    
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                loop {
+                    let seed = next_seed.fetch_add(1, Ordering::Relaxed);
+                    if seed >= start + count {
+                        break;
+                    }
+                    let cfg = Cfg {
+                        seed: seed.to_string(),
+                        difficulty,
+                        plant_options: plant_options.clone(),
+                        ..Default::default()
+                    };
+                    let options = GenerationOptions {threads: 1, ..cfg.generation_options()}; //the seeds are already spread over the threads
+                    let result = match catch_unwind(AssertUnwindSafe(|| RandomisationData::restrictions(hash_str(&cfg.seed), &options, &enum_variants, &fuse_map))) {
+                        Ok(Ok(rand_data)) => SeedResult::Generated(rand_data.stats),
+                        Ok(Err(err)) => SeedResult::Failed(err.to_string()),
+                        Err(payload) => SeedResult::Panicked(
+                            payload.downcast_ref::<String>().cloned()
+                                .or(payload.downcast_ref::<&str>().map(|str| str.to_string()))
+                                .unwrap_or_default()
+                        ),
+                    };
+                    results.lock().unwrap().push((seed, result));
+                }
+            });
+        }
+    });
+    
+    set_hook(default_hook);
+    
+    let mut results = results.into_inner().unwrap();
+    results.sort_unstable_by_key(|(seed, _)| *seed);
+    print!("{}", summarise(&results));
+    
+    Ok(())
+}
+
+fn rules(args: &[String]) -> Result<(), Box<dyn Error>> {
+    match args {
+        [command, path] if command == "check" => {
+            Rules::load_file(path)?;
+            println!("{path} is valid");
+        }
+        [command, path] if command == "export" => {
+            let mut out_file = OpenOptions::new()
+                .create(true)
+                .truncate(true)
+                .write(true)
+                .open(path)?;
+            
+            out_file.write_all(DEFAULT_RULES.as_bytes())?;
+        }
+        _ => return Err(Box::new(CommonError::critical(&format!("Unknown rules command\n{USAGE}")))),
+    }
+    
+    Ok(())
+}
+
+fn summarise(results: &[(u64, SeedResult)]) -> String {
+    let mut out = String::new();
+    let mut totals = GenerationStats::default();
+    let mut first_plants: FxHashMap<Unlockable, u32> = HashMap::default();
+    let mut failures = 0;
+    
+    for (seed, result) in results {
+        match result {
+            SeedResult::Generated(stats) => {
+                format_to!(out,
+                    "{seed}: ok, first plant {:?}, hard zombies {}, bad plants {}, unaffordable {}, no water solution {}, insufficient water solution {}, no pot {}, four flag {}\n",
+                    stats.first_plant.unwrap(),
+                    stats.hard_zombies,
+                    stats.bad_plants,
+                    stats.unaffordable,
+                    stats.no_water_solution,
+                    stats.insufficient_water_solution,
+                    stats.no_pot,
+                    stats.four_flag,
+                );
+                totals.hard_zombies                += stats.hard_zombies;
+                totals.bad_plants                  += stats.bad_plants;
+                totals.unaffordable                += stats.unaffordable;
+                totals.no_water_solution           += stats.no_water_solution;
+                totals.insufficient_water_solution += stats.insufficient_water_solution;
+                totals.no_pot                      += stats.no_pot;
+                totals.four_flag                   += stats.four_flag;
+                *first_plants.entry(stats.first_plant.unwrap()).or_default() += 1;
+            }
+            SeedResult::Failed(msg) => {
+                format_to!(out, "{seed}: FAILED, {msg}\n");
+                failures += 1;
+            }
+            SeedResult::Panicked(msg) => {
+                format_to!(out, "{seed}: PANICKED, {msg}\n");
+                failures += 1;
+            }
+        }
+    }
+    
+    let succeeded = (results.len() - failures).max(1) as f64;
+    format_to!(out, "\nSummary\n");
+    format_to!(out, "    Seeds:     {}\n", results.len());
+    format_to!(out, "    Succeeded: {}\n", results.len() - failures);
+    format_to!(out, "    Failed:    {failures}\n");
+    format_to!(out, "    Average placed levels each reason modified per seed:\n");
+    for (name, total) in [
+        ("HardZombies",               totals.hard_zombies),
+        ("BadPlants",                 totals.bad_plants),
+        ("Unaffordable",              totals.unaffordable),
+    ] {
+        format_to!(out, "        {name:<26} {:.2}\n", total as f64 / succeeded);
+    }
+    format_to!(out, "    Average times each reason ruled out a level per seed:\n");
+    for (name, total) in [
+        ("NoWaterSolution",           totals.no_water_solution),
+        ("InsufficientWaterSolution", totals.insufficient_water_solution),
+        ("NoPot",                     totals.no_pot),
+        ("FourFlag",                  totals.four_flag),
+    ] {
+        format_to!(out, "        {name:<26} {:.2}\n", total as f64 / succeeded);
+    }
+    
+    let mut first_plants: Vec<(Unlockable, u32)> = first_plants.into_iter().collect();
+    first_plants.sort_unstable_by_key(|(plant, count)| (u32::MAX - count, *plant as u8));
+    format_to!(out, "    First plants:\n");
+    for (plant, count) in first_plants {
+        format_to!(out, "        {:<26} {count} ({:.1}%)\n", format!("{plant:?}"), count as f64 / succeeded * 100.);
+    }
+    
+    out
+}
+
+pub fn describe(rand_data: &RandomisationData) -> String {
+    let mut out = String::new();
+    
+    format_to!(out, "Level order: {:?}\n", rand_data.level_order);
+    format_to!(out, "Plant order:\n");
+    for (i, slot) in rand_data.plant_order.iter().enumerate().take(Unlockable::CobCannon as usize + 1) {
+        let plant: Unlockable = unsafe { transmute(i as u8) };
+        match slot {
+            0xFF => format_to!(out, "    {plant:?}: never\n"),
+            0    => format_to!(out, "    {plant:?}: start\n"),
+            _    => format_to!(out, "    {plant:?}: {slot}\n"),
+        }
+    }
+    if !rand_data.rerolls.is_empty() {
+        format_to!(out, "Rerolls: {:?}\n", rand_data.rerolls);
+    }
+    if let Some(fusions) = &rand_data.fusions {
+        format_to!(out, "Fusions:\n");
+        let mut fusions: Vec<(&u32, &[u32;2])> = fusions.iter().collect();
+        fusions.sort_unstable();
+        for (fusion, [plant_1, plant_2]) in fusions {
+            format_to!(out, "    {fusion}: {plant_1} + {plant_2}\n");
+        }
+    }
+    
+    let tables = [
+        ("weights",   &rand_data.weights),
+        ("spawns",    &rand_data.spawns),
+        ("freqs",     &rand_data.freqs),
+        ("firerates", &rand_data.firerates),
+        ("cooldowns", &rand_data.cooldowns),
+        ("costs",     &rand_data.costs),
+        ("conveyors", &rand_data.conveyors),
+        ("flags",     &rand_data.flag_counts),
+        ("scenes",    &rand_data.scenes),
+        ("zstats",    &rand_data.zombie_stats),
+        ("damage",    &rand_data.plant_damage),
+        ("sun",       &rand_data.sun_production),
+    ];
+    
+    for (slot, level) in rand_data.level_order.iter().enumerate() {
+        format_to!(out, "\nSlot {}: level {} ({})\n", slot + 1, level, level_label_in_scene(*level as usize, rand_data.scene(*level as usize)));
+        for (name, table) in tables {
+            if let Some(table) = table {
+                format_to!(out, "    {name:<9}");
+                for byte in &table[slot] {
+                    format_to!(out, " {byte:02x}");
+                }
+                out.push('\n');
+            }
+        }
+        if let Some(sound_seeds) = &rand_data.sound_seeds {
+            format_to!(out, "    {:<9} {:016x}\n", "sounds", sound_seeds[slot]);
+        }
+    }
+    
+    out
+}
+
+pub fn read_fuse_map<T: AsRef<Path>>(path: T) -> Result<FxHashMap<u32,[u32;2]>, Box<dyn Error>> {
+    let text = read_to_string(path)?;
+    let mut fuse_map: FxHashMap<u32,[u32;2]> = HashMap::default();
+    
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let ids: Vec<u32> = match line.split_whitespace().map(|num| num.parse()).collect() {
+            Ok(ids) => ids,
+            Err(_) => return Err(Box::new(CommonError::critical(&format!("Fuse map line {} is not a list of numbers", i + 1)))),
+        };
+        let [fusion, plant_1, plant_2] = ids[..] else {
+            return Err(Box::new(CommonError::critical(&format!("Fuse map line {} should have 3 entries", i + 1))));
+        };
+        fuse_map.insert(fusion, [plant_1, plant_2]);
+    }
+    
+    Ok(fuse_map)
+}
+
+pub fn write_fuse_map<T: AsRef<Path>>(out_path: T, fuse_map: &FxHashMap<u32,[u32;2]>) -> Result<(), Box<dyn Error>> {
+    let mut out_txt = OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(out_path)?;
+    
+    let mut fusions: Vec<(&u32, &[u32;2])> = fuse_map.iter().collect();
+    fusions.sort_unstable_by_key(|(fusion, _)| **fusion); //the game reads these in ascending order, so keep that order for identical hash maps
+    
+    out_txt.write_all(b"# fusion plant_1 plant_2\n")?;
+    for (fusion, [plant_1, plant_2]) in fusions {
+        out_txt.write_all(format!("{fusion} {plant_1} {plant_2}\n").as_bytes())?;
+    }
+    
+    Ok(())
+}
@@ -216,14 +216,12 @@ impl Method {
                             '"' => {inquotes = !inquotes}
                             ')' => {inparens = true}
                             '(' => {inparens = false}
-                            ',' => {
-                                if !(inquotes || inparens) {
-                                    if counter > 0 {
-                                        out_string.insert_str(str_pos, &spaces);
-                                        counter -= 1;
-                                    } else {
-                                        out_string.insert_str(str_pos, &spaces[1..]);
-                                    }
+                            ',' if !(inquotes || inparens) => {
+                                if counter > 0 {
+                                    out_string.insert_str(str_pos, &spaces);
+                                    counter -= 1;
+                                } else {
+                                    out_string.insert_str(str_pos, &spaces[1..]);
                                 }
                             }
                             _ => {}
@@ -300,11 +298,9 @@ impl Method {
                     }
                 }
                 FlowControl::Return |
-                FlowControl::Interrupt => {
-                    if last_loc_ip <= instruction.ip() {
-                        last_ret_idx = Some(i);
-                        break;
-                    }
+                FlowControl::Interrupt if last_loc_ip <= instruction.ip() => {
+                    last_ret_idx = Some(i);
+                    break;
                 }
                 _ => {}
             }
@@ -343,6 +339,11 @@ impl Method {
         ret
     }
     
+    pub fn get_entry_instructions(&self, size: u64, off: i64, meta: &IL2CppDumper) -> Result<Vec<Instruction>, String> { //the instructions a hook of size bytes overwrites, if they still work when run from somewhere else
+        let start_off = meta.pe.map_v2p(self.addr).unwrap();
+        entry_instructions(&meta.assembly[start_off .. start_off + self.len as usize], (self.addr as i64 + off) as u64, size)
+    }
+    
     pub fn get_calls(&self, meta: &IL2CppDumper, off: i64, table: &mut FxHashMap<String, u64>) {
         let start_off = meta.pe.map_v2p(self.addr).unwrap();
         let decoder   = Decoder::with_ip(
@@ -624,7 +625,7 @@ impl DecodeSymbolResolver {
     }
 }
 impl SymbolResolver for DecodeSymbolResolver {
-    fn symbol(&mut self, instruction: &Instruction, _operand: u32, instruction_operand: Option<u32>, addr: u64, _addr_size: u32) -> Option<SymbolResult> {
+    fn symbol(&mut self, instruction: &Instruction, _operand: u32, instruction_operand: Option<u32>, addr: u64, _addr_size: u32) -> Option<SymbolResult<'_>> {
         if instruction.next_ip() <= addr {
             if let Some(idx) = self.locs.get(&addr) {
                 format_label(*idx, &mut self.label_name, self.function_name_len, ".loc");
@@ -704,6 +705,39 @@ impl SymbolResolver for DecodeSymbolResolver {
     }
 }
 
+pub fn entry_instructions(code: &[u8], start: u64, size: u64) -> Result<Vec<Instruction>, String> { //the instructions at the start of code that a hook of size bytes overwrites
+    let decoder = Decoder::with_ip(
+        64,
+        code,
+        start,
+        DecoderOptions::NONE
+    );
+    let instructions: Vec<Instruction> = decoder.into_iter().collect();
+    
+    let mut ret = Vec::new();
+    for instruction in instructions.iter().take_while(|instruction| instruction.ip() < start + size) {
+        if instruction.is_invalid() {
+            return Err(format!("invalid instruction at +0x{:X}", instruction.ip() - start));
+        }
+        if !matches!(instruction.flow_control(), FlowControl::Next | FlowControl::Call) {
+            return Err(format!("{:?} at +0x{:X} can't be moved", instruction.mnemonic(), instruction.ip() - start));
+        }
+        ret.push(*instruction);
+    }
+    let end = ret.last().map_or(start, Instruction::next_ip);
+    if end < start + size {
+        return Err(format!("the method is only {} bytes long", end - start));
+    }
+    
+    for instruction in &instructions {
+        if matches!(instruction.op0_kind(), OpKind::NearBranch64) && (start+1 .. end).contains(&instruction.near_branch64()) {
+            return Err(format!("+0x{:X} jumps to +0x{:X}, which the hook overwrites", instruction.ip() - start, instruction.near_branch64() - start));
+        }
+    }
+    
+    Ok(ret)
+}
+
 fn format_label(number: usize, label_name: &mut String, function_name_len: usize, kind: &str) {
     let mut tmp_idx = number;
     label_name.replace_range(function_name_len.., kind);
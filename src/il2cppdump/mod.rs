@@ -1396,6 +1396,12 @@ impl IL2CppDumper {
         }
     }
     
+    pub fn enum_variants(&self) -> FxHashMap<String, u64> {
+        let mut table: FxHashMap<String, u64> = HashMap::with_capacity_and_hasher(16384, BuildHasherDefault::default());
+        self.get_enum_variants(&mut table);
+        table
+    }
+    
     pub fn output_disasm<T: AsRef<Path>>(self : &Arc<Self>, out_path: T) -> Result<(), Box<dyn Error>> {
         let mut sorted: Vec<u32> = Vec::with_capacity(self.methods_array.len());
         for i in 0..self.methods_array.len() {
@@ -1,11 +1,12 @@
-use std::{collections::{HashMap, HashSet}, hash::{BuildHasherDefault, Hash}, mem::transmute, ops::Not, sync::OnceLock};
+use std::{collections::{HashMap, HashSet}, error::Error, fmt::Display, hash::{BuildHasherDefault, Hash}, mem::transmute, ops::Not, sync::Arc, thread::{self, available_parallelism}};
 
 use arrayvec::ArrayVec;
 use fxhash::{FxHashMap, FxHashSet};
 use rand::RngCore;
 use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
 use smallvec::SmallVec;
-use crate::{data::{LevelData, LevelType, Unlockable, ZombieFlags, ZombieLanes, ZombieType, COOLDOWN_TABLE, LEVEL_DATA}, il2cppdump::IL2CppDumper, util::hash_str};
+use serde::{Deserialize, Serialize};
+use crate::{data::{LevelData, LevelType, Unlockable, ZombieFlags, ZombieLanes, ZombieType, COOLDOWN_TABLE, LEVEL_DATA, PLANT_DATA}, difficulty::{Difficulty, DifficultyParams}, plan::{Plan, PlannedPlant}, plant_options::PlantOptions, rules::RULES, util::hash_str};
 use crate::data::ZOMBIE_DATA;
 
 pub struct RandomisationData {
@@ -18,9 +19,74 @@ pub struct RandomisationData {
     pub costs:         Option<Vec<Vec<u8>>>,
     pub spawns:        Option<Vec<Vec<u8>>>,
     pub freqs:         Option<Vec<Vec<u8>>>,
+    pub conveyors:     Option<Vec<Vec<u8>>>, //weight of each menu plant on conveyor levels, empty for other levels
+    pub flag_counts:   Option<Vec<Vec<u8>>>, //a single byte for each level
+    pub scenes:        Option<Vec<Vec<u8>>>, //a single LevelType byte for each level
+    pub zombie_stats:  Option<Vec<Vec<u8>>>, //health, speed then damage multipliers, ZOMBIE_STAT_STRIDE bytes each
+    pub plant_damage:  Option<Vec<Vec<u8>>>, //indexed like firerates
+    pub sun_production: Option<Vec<Vec<u8>>>, //indexed like firerates
+    pub fusions:       Option<FxHashMap<u32,[u32;2]>>, //shuffled recipes, keyed by the fused plant's id
+    pub weight_scaling: Option<Vec<(f32, f32)>>, //normal and elite zombie weight multipliers for each slot
+    pub rerolls:       Vec<(u8, u8)>, //the reroll counts used, which go past the ones asked for when those give impossible levels
+    pub stats:         GenerationStats,
     restrictions_data: Option<RestrictionsData>,
 }
 
+#[derive(Clone, Default)]
+pub struct GenerationOptions {
+    pub difficulty:    Difficulty,
+    pub plant_options: PlantOptions, //only used with restrictions
+    pub conveyors:     bool,
+    pub flag_counts:   bool,
+    pub scenes:        bool,
+    pub zombie_stats:  bool,
+    pub plant_damage:  bool,
+    pub sun_production: bool,
+    pub fusions:       bool,
+    pub progressive_weights: bool,
+    pub rerolls:       Vec<(u8, u8)>, //how many times each slot is rerolled, slots count from 1 in the order levels are played, only used with restrictions
+    pub plan:          Option<Plan>, //only used with restrictions
+    pub threads:       usize, //threads used for level checks with restrictions, 0 uses every core, the result doesn't depend on it
+}
+
+#[derive(Default, Clone)]
+pub struct GenerationStats {
+    pub no_water_solution:           u32,
+    pub insufficient_water_solution: u32,
+    pub no_pot:                      u32,
+    pub four_flag:                   u32,
+    pub hard_zombies:                u32, //these three count placed levels that were modified, not every level that was checked
+    pub bad_plants:                  u32,
+    pub unaffordable:                u32,
+    pub first_plant:      Option<Unlockable>,
+    pub chosen_solutions: Vec<(Unlockable, f32)>,
+    pub blacklist:        Vec<u32>,
+    pub unlock_order:     Vec<Unlockable>,
+}
+
+struct PlantAttrRngs {
+    cost:      ChaCha8Rng,
+    cooldowns: ChaCha8Rng,
+    firerates: ChaCha8Rng,
+}
+
+impl PlantAttrRngs {
+    fn new(seed: u64) -> Self {
+        Self {
+            cost:      ChaCha8Rng::seed_from_u64(seed ^ hash_str("plant_cost")),
+            cooldowns: ChaCha8Rng::seed_from_u64(seed ^ hash_str("plant_cooldowns")),
+            firerates: ChaCha8Rng::seed_from_u64(seed ^ hash_str("plant_firerates")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct LevelModifications { //what changed a level's zombies or plants, only counted in the stats once the level is placed
+    hard_zombies: bool,
+    bad_plants:   bool,
+    unaffordable: bool,
+}
+
 #[derive(Clone)]
 struct LevelPlants {
     menu: Vec<(u8, u8)>,
@@ -33,8 +99,73 @@ struct RestrictionsData {
     modified_level_spawns: FxHashMap<u8, Vec<(u32,u32)>>,
     level_plants: FxHashMap<u8, LevelPlants>,
     modified_level_plants: FxHashMap<u8, LevelPlants>,
+    level_modifications: FxHashMap<u8, LevelModifications>, //kept alongside the modified spawns and plants
     plant_map: FxHashMap<String, u32>,
     unlocked_plants: FxHashSet<Unlockable>,
+    excluded_plants: FxHashSet<Unlockable>,
+    early_levels: Vec<u8>, //levels with early plants that haven't been placed yet
+    early_deadline: usize,
+    conveyor_pools: FxHashMap<u8, (Vec<u8>, FxHashSet<Unlockable>)>,
+    flag_counts: FxHashMap<u8, u8>,
+    scenes: Vec<LevelType>, //indexed by level
+    zombie_stats: Vec<Vec<u8>>, //indexed by level, empty if zombie stats aren't randomised
+    plant_damage: Vec<Vec<u8>>, //indexed by level, empty if plant damage isn't randomised
+    level_sun: Vec<Vec<u8>>, //indexed by level, empty if sun production isn't randomised
+    planned_levels: FxHashMap<u8, u8>, //the slot of each level the plan places that hasn't been placed yet
+    authored_spawns: FxHashSet<u8>, //levels with zombies from the plan, which are never lowered
+    authored_plants: FxHashMap<u8, Vec<PlannedPlant>>, //plant stats from the plan in each level, which are never capped
+    progressive_weights: bool,
+    threads: usize,
+}
+
+impl RestrictionsData {
+    fn authored_plant(&self, level_idx: u8, plant: Unlockable) -> Option<&PlannedPlant> {
+        self.authored_plants.get(&level_idx)?.iter().find(|planned| planned.plant == plant)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum GenerationError {
+    ImpossibleLevels(Vec<usize>),
+    MissingPlant(Unlockable),
+    MissingZombie(ZombieType),
+    MissingLevelData(usize),
+    NoFlags(usize),
+    UnsupportedFlags(usize, u8),
+    NoZombies(usize),
+    EmptyChoice(&'static str),
+    ExcludedPlant(Unlockable),
+    InvalidFirstPlant(Unlockable),
+    TooManyEarlyPlants(usize, u8),
+    NoAllowedSolution(Problem),
+    InvalidReroll(u8),
+    InvalidPlan(String),
+    PlannedLevelImpossible(u8, usize),
+}
+
+impl Display for GenerationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ImpossibleLevels(levels) => write!(f, "These levels were impossible to satisfy: {levels:?}"),
+            Self::MissingPlant(plant)      => write!(f, "{plant:?} is missing from the plant enum"),
+            Self::MissingZombie(zombie)    => write!(f, "Zombie type does not exist: \"{zombie:?}\""),
+            Self::MissingLevelData(level)  => write!(f, "Level {level} has no generated data"),
+            Self::NoFlags(level)           => write!(f, "Level {level} has no flag count"),
+            Self::UnsupportedFlags(level, flags) => write!(f, "Level {level} has an unsupported flag count of {flags}"),
+            Self::NoZombies(level)         => write!(f, "Level {level} has no zombies that can spawn"),
+            Self::EmptyChoice(what)        => write!(f, "There was nothing to choose from when picking {what}"),
+            Self::ExcludedPlant(plant)     => write!(f, "{plant:?} is needed, so it can't be excluded"),
+            Self::InvalidFirstPlant(plant) => write!(f, "{plant:?} can't be the first plant"),
+            Self::TooManyEarlyPlants(plants, levels) => write!(f, "{plants} early plants can't all be unlocked in the first {levels} levels"),
+            Self::NoAllowedSolution(problem) => write!(f, "Every solution to {problem:?} uses an excluded plant"),
+            Self::InvalidReroll(slot)      => write!(f, "Slot {slot} can't be rerolled, only slots 2 to 45 can"),
+            Self::InvalidPlan(reason)      => write!(f, "The plan can't be used: {reason}"),
+            Self::PlannedLevelImpossible(slot, level) => write!(f, "Level {level} is impossible in slot {slot} with the plants unlocked before it"),
+        }
+    }
+}
+
+impl Error for GenerationError {
 }
 
 #[allow(dead_code)]
@@ -45,11 +176,12 @@ enum ImpossibleReason {
     FourFlag,
     HardZombies(f64, FxHashMap<u32,u32>),
     BadPlants(f64, Vec<(Unlockable,u8,u8,u8)>),
+    Unaffordable(f64, Vec<(Unlockable,u8)>), //the highest cost byte each plant can have
 }
 
 #[allow(dead_code)]
-#[derive(Clone, Copy, Eq, Hash, PartialEq)]
-enum Problem {
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum Problem {
     Water1,
     Water2,
     Water34,
@@ -74,6 +206,14 @@ pub struct FrequencyData {
     first_2_flag_max: FxHashMap<u32, f32>,
     first_wave_occurence_avgs: FxHashMap<u32, u32>,
     totals: Vec<u8>,
+    counts: Arc<ExpectedCounts>,
+}
+
+struct ExpectedCounts { //the tables behind a FrequencyData, so the same level with a few weights changed can start from them
+    spawn_vec_pre_10: Vec<(u32, u32, u32)>,
+    pre_10:           Option<Vec<f64>>,
+    spawn_vec:        Vec<(u32, u32, u32)>,
+    all:              Option<Vec<f64>>,
 }
 
 #[derive(Hash, Clone, Eq, PartialEq)]
@@ -91,12 +231,12 @@ struct ProblemData {
 
 #[allow(dead_code)]
 #[derive(Clone, Debug)]
-struct SolutionEntry {
-    uses:   Option<u8>,
-    negate: bool,
-    night:  bool,
-    weight: f32,
-    plants: ArrayVec<Unlockable, 4>,
+pub struct SolutionEntry {
+    pub uses:   Option<u8>,
+    pub negate: bool,
+    pub night:  bool,
+    pub weight: f32,
+    pub plants: ArrayVec<Unlockable, 4>,
 }
 impl Default for SolutionEntry {
     fn default() -> Self {
@@ -131,70 +271,89 @@ impl Hash for SolutionEntry {
     }
 }
 impl SolutionEntry {
-    #[allow(dead_code)]
-    fn compute_new_plant_utilization(&self, modifiers: &LevelPlants, usage_set: &mut [f32], zombie_cooldown: f32) -> f32 { //outputs zombie _frequency_
-        let solution_cooldown = self.compute_solution_cooldown(modifiers, usage_set);
-        let max_cooldown = solution_cooldown.max(zombie_cooldown);
-        if max_cooldown != f32::INFINITY {
-            let mut duplicate_set: FxHashSet<Unlockable> = FxHashSet::with_capacity_and_hasher(4, BuildHasherDefault::default());
-            for (i, plant) in self.plants.iter().copied().enumerate() {
-                for plant_2 in self.plants.iter().copied().skip(i + 1) {
-                    if plant == plant_2 {
-                        duplicate_set.insert(plant);
-                    }
-                }
-            }
-            
-            let plant_set: FxHashSet<Unlockable> = self.plants.iter().copied().collect();
-            for plant in plant_set.iter().copied() {
-                let (_, cooldown_mul) = modifiers.menu[plant as usize];
-                let mut cooldown = COOLDOWN_TABLE[plant as usize] / (1. - usage_set[plant as usize]) * mul_from_u8(cooldown_mul);
-                if plant != Unlockable::DoomShroom && !duplicate_set.contains(&plant) {
-                    cooldown *= 0.5;
+    fn plant_demand(&self) -> ArrayVec<(Unlockable, f32), 4> { //plants placed per zombie answered, an empty list means nothing needs replanting
+        let mut demand: ArrayVec<(Unlockable, f32), 4> = ArrayVec::new();
+        if let Some(uses) = self.uses {
+            for plant in self.plants.iter().copied() {
+                if let Some((_, count)) = demand.iter_mut().find(|(p, _)| *p == plant) {
+                    *count += 1. / uses as f32;
+                } else {
+                    demand.push((plant, 1. / uses as f32));
                 }
-                usage_set[plant as usize] = cooldown / max_cooldown; // TODO: this math feels unsound, i was very out of it while writing this
             }
-            
-            1. / (1. / zombie_cooldown - 1. / max_cooldown)
-        } else {
-            zombie_cooldown
         }
+        demand
     }
     
-    fn compute_solution_cooldown(&self, modifiers: &LevelPlants, usage_set: &[f32]) -> f32 {
-        let mut max_cooldown  = 0f32;
-        let mut slowest_plant = Unlockable::Peashooter;
-        let mut plant_set: FxHashSet<Unlockable> = FxHashSet::with_capacity_and_hasher(4, BuildHasherDefault::default());
-        for plant in self.plants.iter().copied() {
-            let (_, cooldown_mul) = modifiers.menu[plant as usize];
-            let mut cooldown = COOLDOWN_TABLE[plant as usize] / (1. - usage_set[plant as usize]);
-            cooldown *= mul_from_u8(cooldown_mul);
-            if plant != Unlockable::DoomShroom && !plant_set.contains(&plant) {
-                cooldown *= 0.5;
-            }
-            if cooldown > max_cooldown {
-                slowest_plant = plant;
-                max_cooldown = cooldown;
-            }
-            plant_set.insert(plant);
-        }
-        if slowest_plant != Unlockable::SmallPuff && plant_set.contains(&Unlockable::SmallPuff) {
-            max_cooldown /= 3.;
-        }
-        
-        if let Some(uses) = self.uses {
-            max_cooldown / uses as f32
-        } else {
-            0.
+    fn plant_cost(plant: Unlockable, modifiers: &LevelPlants) -> f32 {
+        let (_, cost_mul) = modifiers.menu[plant as usize];
+        PLANT_DATA[plant as usize].cost as f32 * mul_from_u8(cost_mul)
+    }
+    
+    fn sun_cost(&self, modifiers: &LevelPlants) -> f32 {
+        self.plants.iter().map(|plant| Self::plant_cost(*plant, modifiers)).sum()
+    }
+    
+    fn plant_cooldown(plant: Unlockable, modifiers: &LevelPlants) -> f32 {
+        let (cooldown_mul, _) = modifiers.menu[plant as usize];
+        COOLDOWN_TABLE[plant as usize] * mul_from_u8(cooldown_mul)
+    }
+    
+    //answers as much of a threat arriving at zombie_freq zombies a wave as the plants can recharge for, marking the recharges as used
+    //returns the frequency that is still unanswered
+    fn answer_threat(&self, modifiers: &LevelPlants, usage_set: &mut [f32], zombie_freq: f32) -> f32 {
+        let demand = self.plant_demand();
+        let mut answered = zombie_freq;
+        for (plant, count) in &demand {
+            let free = (1. - usage_set[*plant as usize]).max(0.);
+            answered = answered.min(free * WAVE_SECONDS / (count * Self::plant_cooldown(*plant, modifiers)));
+        }
+        for (plant, count) in &demand {
+            usage_set[*plant as usize] += answered * count * Self::plant_cooldown(*plant, modifiers) / WAVE_SECONDS;
+        }
+        zombie_freq - answered
+    }
+    
+    fn compute_solution_cooldown(&self, modifiers: &LevelPlants, usage_set: &[f32]) -> f32 { //seconds between zombies this can answer with the recharges left
+        let mut max_cooldown = 0f32;
+        for (plant, count) in self.plant_demand() {
+            let free = 1. - usage_set[plant as usize];
+            let cooldown = if free > 0. {
+                count * Self::plant_cooldown(plant, modifiers) / free
+            } else {
+                f32::INFINITY
+            };
+            max_cooldown = max_cooldown.max(cooldown);
         }
+        max_cooldown
     }
 }
 
-type Solutions = Box<[Box<[Unlockable]>]>;
+pub type Solutions = Box<[Box<[Unlockable]>]>;
+
+pub const ZOMBIE_STAT_STRIDE: usize = 128; //the size of each of the three parts of a zombie stat table, matching zombie_stat_table
+const BUFFED_HEALTH: f32 = 1.5; //zombies with at least this much extra health count as one health tier higher
+const WAVE_SECONDS: f32 = 25.; //roughly how long each wave has before the next one arrives
+const STARTING_SUN: f32 = 50.;
+const SKY_SUN: f32 = 25. / 10.; //sun a second falling from the sky on levels that aren't at night
+//Marigold makes coins rather than sun, and SmallPuff is the free puff-shroom rather than a sun-shroom, which isn't one of the unlockables, so night levels only have sunflowers too
+const SUN_PRODUCERS: [(Unlockable, f32); 1] = [ //sun a second each one makes at normal sun production
+    (Unlockable::SunFlower, 25. / 24.),
+];
+const MAX_PRODUCERS: usize = 10; //more than this doesn't leave much of the lawn for anything else
+
+pub const PROBLEM_FLAGS: ZombieFlags = ZombieFlags::HIGH_HEALTH
+    .union(ZombieFlags::V_HIGH_HEALTH)
+    .union(ZombieFlags::FLIES)
+    .union(ZombieFlags::GARG_TYPE)
+    .union(ZombieFlags::EVIL_DEATH);
 
 impl RandomisationData {
-    pub fn no_restrictions(seed: u64, meta: &IL2CppDumper, fuse_data: &FxHashMap<u32,[u32;2]>) -> Self {
-        let plant_ids     = Self::get_plant_ids(meta);
+    pub fn no_restrictions(seed: u64, options: &GenerationOptions, enum_variants: &FxHashMap<String, u64>, fuse_data: &FxHashMap<u32,[u32;2]>) -> Result<Self, GenerationError> {
+        let fusions       = Self::randomise_fusions(seed, options.fusions, fuse_data);
+        let fuse_data     = fusions.as_ref().unwrap_or(fuse_data);
+        let params        = options.difficulty.params();
+        let plant_ids     = Self::get_plant_ids(enum_variants);
         let level_order   = Self::randomise_level_order_no_restrictions(seed);
         let plant_order   = Self::randomise_plant_order_no_restrictions(seed);
         let mut weights   = Vec::new();
@@ -203,6 +362,18 @@ impl RandomisationData {
         let mut cooldowns = Vec::new();
         let mut costs     = Vec::new();
         let mut spawns    = Vec::new();
+        let mut conveyor_pools = Vec::new();
+        let mut conveyor_rng   = ChaCha8Rng::seed_from_u64(seed ^ hash_str("conveyors"));
+        let level_flags        = Self::randomise_flag_counts(seed, options.flag_counts);
+        let mut flag_counts    = Vec::new();
+        let level_scenes       = Self::randomise_scenes(seed, options.scenes);
+        let mut scenes         = Vec::new();
+        let level_zombie_stats = Self::randomise_zombie_stats(seed, options.zombie_stats, &params);
+        let mut zombie_stats   = Vec::new();
+        let level_plant_damage = Self::randomise_plant_stat(seed, "plant_damage", options.plant_damage, Unlockable::Peashooter, enum_variants, fuse_data, &params);
+        let mut plant_damage   = Vec::new();
+        let level_sun          = Self::randomise_plant_stat(seed, "sun_production", options.sun_production, Unlockable::SunFlower, enum_variants, fuse_data, &params);
+        let mut sun_production = Vec::new();
         
         weights.push(vec![1, 0, 0, 0]);
         freqs.push(104f32.to_ne_bytes().to_vec());
@@ -210,44 +381,70 @@ impl RandomisationData {
         cooldowns.push(vec![0; 2]);
         costs.push(vec![0; 2]);
         spawns.push(vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        conveyor_pools.push(Vec::new());
+        flag_counts.push(vec![level_flags[0].unwrap_or(0)]);
+        scenes.push(vec![level_scenes[0] as u8]);
+        zombie_stats.push(level_zombie_stats[0].clone());
+        plant_damage.push(level_plant_damage[0].clone());
+        sun_production.push(level_sun[0].clone());
         
         for (level_true_idx, level_idx) in (2..=45).zip(level_order.iter().skip(1)) {
-            weights.push(
-                Self::randomise_weights_no_restrictions(
-                    seed ^ hash_str(&level_true_idx.to_string()),
-                )
+            let level = &LEVEL_DATA.get().unwrap()[*level_idx as usize - 1];
+            let flags = level_flags[*level_idx as usize - 1];
+            let scene = level_scenes[*level_idx as usize - 1];
+            flag_counts.push(vec![flags.unwrap_or(0)]);
+            scenes.push(vec![scene as u8]);
+            zombie_stats.push(level_zombie_stats[*level_idx as usize - 1].clone());
+            plant_damage.push(level_plant_damage[*level_idx as usize - 1].clone());
+            sun_production.push(level_sun[*level_idx as usize - 1].clone());
+            conveyor_pools.push(if options.conveyors && level.conveyor_plants.is_some() {
+                Self::randomise_conveyor_pool(level, flags, &mut conveyor_rng).0
+            } else {
+                Vec::new()
+            });
+            let mut level_weights = Self::randomise_weights_no_restrictions(
+                seed ^ hash_str(&level_true_idx.to_string()),
+                &params,
             );
+            if options.progressive_weights {
+                Self::scale_weight_bytes(&mut level_weights, level_true_idx - 1);
+            }
+            weights.push(level_weights);
             firerates.push(
                 Self::randomise_firerates_no_restrictions(
                     seed ^ hash_str(&level_true_idx.to_string()),
                     &plant_ids,
                     fuse_data,
+                    &params,
                 )
             );
             cooldowns.push(
                 Self::randomise_cooldowns_no_restrictions(
                     seed ^ hash_str(&level_true_idx.to_string()),
+                    &params,
                 )
             );
             costs.push(
                 Self::randomise_costs_no_restrictions(
                     seed ^ hash_str(&level_true_idx.to_string()),
+                    &params,
                 )
             );
             spawns.push(
                 Self::randomise_spawns_no_restrictions(
                     seed ^ hash_str(&level_true_idx.to_string()),
                     *level_idx as usize,
-                    level_true_idx,
+                    scene,
+                    level_true_idx > 45 - params.odyssey_levels,
                 )
             );
-            let data = Self::compute_zombie_freq_data_bytes(&spawns[level_true_idx - 1], &weights[level_true_idx - 1], *level_idx as usize).unwrap();
+            let data = Self::compute_zombie_freq_data_bytes(&spawns[level_true_idx - 1], &weights[level_true_idx - 1], *level_idx as usize, flags)?;
             freqs.push(data.totals);
         }
         
         let sound_seeds = Self::randomise_sounds(seed ^ hash_str("Sounds"));
         
-        Self {
+        Ok(Self {
             level_order,
             plant_order,
             weights:     Some(weights),
@@ -256,20 +453,27 @@ impl RandomisationData {
             costs:       Some(costs),
             spawns:      Some(spawns),
             freqs:       Some(freqs),
+            conveyors:   options.conveyors.then_some(conveyor_pools),
+            flag_counts: options.flag_counts.then_some(flag_counts),
+            scenes:      options.scenes.then_some(scenes),
+            zombie_stats: options.zombie_stats.then_some(zombie_stats),
+            plant_damage: options.plant_damage.then_some(plant_damage),
+            sun_production: options.sun_production.then_some(sun_production),
+            fusions,
+            weight_scaling: options.progressive_weights.then(Self::weight_scaling_table),
             sound_seeds: Some(sound_seeds),
+            rerolls:     Vec::new(),
+            stats:       GenerationStats::default(),
             restrictions_data: None,
-        }
+        })
     }
     
-    fn get_plant_ids(meta: &IL2CppDumper) -> Vec<u32> {
-        let mut enum_variants: FxHashMap<String, u64> = HashMap::with_capacity_and_hasher(16384, BuildHasherDefault::default());
+    fn get_plant_ids(enum_variants: &FxHashMap<String, u64>) -> Vec<u32> {
         let mut plant_ids: Vec<u32> = Vec::with_capacity(384);
         
-        meta.get_enum_variants(&mut enum_variants);
-        
         for (name, val) in enum_variants {
-            if name.starts_with("PlantType::") && val as i64 >= 0 {
-                plant_ids.push(val as u32);
+            if name.starts_with("PlantType::") && *val as i64 >= 0 {
+                plant_ids.push(*val as u32);
             }
         }
         
@@ -287,12 +491,12 @@ impl RandomisationData {
         bitfield[bit >> 3] ^= 1 << (bit & 7) as u8;
     }
     
-    fn randomise_weights_no_restrictions(seed: u64) -> Vec<u8> {
+    fn randomise_weights_no_restrictions(seed: u64, params: &DifficultyParams) -> Vec<u8> {
         let mut rng = ChaCha8Rng::seed_from_u64(seed.wrapping_add(hash_str("zombie_weights")));
         let mut ret = Vec::with_capacity(ZOMBIE_DATA.get().unwrap().len() * 4);
         
         for zombie in ZOMBIE_DATA.get().unwrap() {
-            let weight_mul = 10f64.powf(Self::weight_curve(rng.next_u32()));
+            let weight_mul = 10f64.powf(Self::weight_curve(rng.next_u32()) * params.weight_spread);
             for byte in ((weight_mul * zombie.default_weight as f64).round() as i32).to_le_bytes() {
                 ret.push(byte);
             }
@@ -301,7 +505,51 @@ impl RandomisationData {
         ret
     }
     
-    fn randomise_firerates_no_restrictions(seed: u64, plant_ids: &[u32], fuse_data: &FxHashMap<u32,[u32;2]>) -> Vec<u8> {
+    pub fn slot_weight_scaling(slot: usize) -> (f64, f64) { //normal and elite multipliers for every zombie but the basic one, slot is 0 indexed and the first one is never changed
+        if slot == 0 {
+            return (1., 1.);
+        }
+        let normal = 0.5 + (slot - 1) as f64 / 43.;
+        (normal, normal * normal)
+    }
+    
+    fn weight_scaling_table() -> Vec<(f32, f32)> {
+        (0..45).map(|slot| {
+            let (normal, elite) = Self::slot_weight_scaling(slot);
+            (normal as f32, elite as f32)
+        }).collect()
+    }
+    
+    fn zombie_weight_mul(slot: usize, zombie_idx: u32) -> f64 {
+        if zombie_idx == 0 {
+            return 1.;
+        }
+        let (normal, elite) = Self::slot_weight_scaling(slot);
+        if ZOMBIE_DATA.get().unwrap()[zombie_idx as usize].flags.contains(ZombieFlags::IS_ELITE) {elite} else {normal}
+    }
+    
+    fn scale_weight(weight: u32, mul: f64) -> u32 { //never scales a zombie out of the level
+        if weight == 0 {
+            return 0;
+        }
+        ((weight as f64 * mul).round() as u32).max(1)
+    }
+    
+    fn scale_weight_bytes(weights: &mut [u8], slot: usize) {
+        for (i, bytes) in weights.chunks_exact_mut(4).enumerate() {
+            let weight = i32::from_le_bytes((&*bytes).try_into().unwrap()).max(0) as u32;
+            let weight = Self::scale_weight(weight, Self::zombie_weight_mul(slot, i as u32));
+            bytes.copy_from_slice(&(weight as i32).to_le_bytes());
+        }
+    }
+    
+    fn scale_spawns(spawns: &mut [(u32, u32)], slot: usize) {
+        for (idx, weight) in spawns.iter_mut() {
+            *weight = Self::scale_weight(*weight, Self::zombie_weight_mul(slot, *idx));
+        }
+    }
+    
+    fn randomise_firerates_no_restrictions(seed: u64, plant_ids: &[u32], fuse_data: &FxHashMap<u32,[u32;2]>, params: &DifficultyParams) -> Vec<u8> {
         let mut rng = ChaCha8Rng::seed_from_u64(seed.wrapping_add(hash_str("plant_firerates")));
         
         let mut ret = vec![0u8; plant_ids.len()];
@@ -318,13 +566,16 @@ impl RandomisationData {
         for (byte, rbyte) in remainder.iter_mut().zip(rand_bytes.iter()) {
             *byte = *rbyte;
         }
+        for byte in ret.iter_mut() {
+            *byte = params.scale_mul(*byte);
+        }
         
         Self::set_fusion_firerates(&mut ret, plant_ids, fuse_data);
         
         ret
     }
     
-    fn randomise_cooldowns_no_restrictions(seed: u64) -> Vec<u8> {
+    fn randomise_cooldowns_no_restrictions(seed: u64, params: &DifficultyParams) -> Vec<u8> {
         let mut rng = ChaCha8Rng::seed_from_u64(seed.wrapping_add(hash_str("plant_cooldowns")));
         
         let mut ret = vec![0u8; 48];
@@ -332,7 +583,7 @@ impl RandomisationData {
             let val = rng.next_u64();
             let rand_bytes = val.to_le_bytes();
             for (byte, rbyte) in bytes.iter_mut().zip(rand_bytes.iter()) {
-                *byte = *rbyte;
+                *byte = params.scale_mul(*rbyte);
             }
         }
         ret[1] = u8::min(ret[1], 128);
@@ -340,7 +591,7 @@ impl RandomisationData {
         ret
     }
     
-    fn randomise_costs_no_restrictions(seed: u64) -> Vec<u8> {
+    fn randomise_costs_no_restrictions(seed: u64, params: &DifficultyParams) -> Vec<u8> {
         let mut rng = ChaCha8Rng::seed_from_u64(seed.wrapping_add(hash_str("plant_costs")));
         
         let mut ret = vec![0u8; 48];
@@ -348,7 +599,7 @@ impl RandomisationData {
             let val = rng.next_u64();
             let rand_bytes = val.to_le_bytes();
             for (byte, rbyte) in bytes.iter_mut().zip(rand_bytes.iter()) {
-                *byte = *rbyte;
+                *byte = params.scale_mul(*rbyte);
             }
         }
         ret[1] = u8::min(ret[1], 128);
@@ -356,7 +607,7 @@ impl RandomisationData {
         ret
     }
     
-    fn randomise_spawns_no_restrictions(seed: u64, level_idx: usize, level_true_idx: usize) -> Vec<u8> {
+    fn randomise_spawns_no_restrictions(seed: u64, level_idx: usize, scene: LevelType, allow_odyssey: bool) -> Vec<u8> {
         let mut rng = ChaCha8Rng::seed_from_u64(seed.wrapping_add(hash_str("zombie_spawns")));
         let mut ret = vec![0u8; 16];
         let level = &LEVEL_DATA.get().unwrap()[level_idx - 1];
@@ -367,13 +618,14 @@ impl RandomisationData {
         
         for (i, zombie) in ZOMBIE_DATA.get().unwrap().iter().enumerate() {
             if let ZombieLanes::Water = zombie.allowed_lanes {
-                match level.level_type {
-                    LevelType::Pool |
-                    LevelType::Fog => {}
-                    _ => continue
+                if !scene.has_water() {
+                    if ret[i >> 3] & 1 << (i & 7) != 0 { //the level's own water zombies can't spawn in a dry scene
+                        Self::xor_bit_in_bitfield(i, &mut ret);
+                    }
+                    continue;
                 }
             }
-            if (zombie.flags.contains(ZombieFlags::IS_ODYSSEY) && level_true_idx <= 30) || zombie.flags.contains(ZombieFlags::IS_BANNED) {
+            if (zombie.flags.contains(ZombieFlags::IS_ODYSSEY) && !allow_odyssey) || zombie.flags.contains(ZombieFlags::IS_BANNED) {
                 continue;
             }
             
@@ -479,6 +731,200 @@ impl RandomisationData {
         }
     }
     
+    fn randomise_conveyor_pool(level: &LevelData, flags: Option<u8>, rng: &mut ChaCha8Rng) -> (Vec<u8>, FxHashSet<Unlockable>) { //starts from a solution to the level type so most pools are usable
+        let solutions = &RULES.get().unwrap().all_solutions;
+        let mut pool: FxHashSet<Unlockable> = HashSet::default();
+        
+        let problem = match (level.level_type, flags) {
+            (LevelType::Pool | LevelType::Fog, Some(1)) => Some(Problem::Water1),
+            (LevelType::Pool | LevelType::Fog, Some(2)) => Some(Problem::Water2),
+            (LevelType::Pool | LevelType::Fog, _) => Some(Problem::Water34),
+            (LevelType::Roof, _) if level.flags != Some(1) => Some(Problem::Roof), //only the levels that start with one flag have pots placed
+            _ => None,
+        };
+        if let Some(options) = problem.and_then(|problem| solutions.get(&problem)) {
+            let solution = &options[((rng.next_u32() as u64 * options.len() as u64) >> 32) as usize];
+            for plant in solution {
+                pool.insert(*plant);
+                pool.insert(Self::upgrade_to_plant(*plant));
+            }
+        }
+        
+        let candidates: SmallVec<[Unlockable; 48]> = (0..=Unlockable::CobCannon as u8)
+            .filter_map(Unlockable::from_u8)
+            .filter(|plant| !matches!(plant, Unlockable::SunFlower | Unlockable::Marigold) && Self::upgrade_to_plant(*plant) == *plant) //conveyor levels have no sun
+            .collect();
+        let size = 6 + (rng.next_u32() % 5) as usize;
+        while pool.len() < size {
+            pool.insert(candidates[((rng.next_u32() as u64 * candidates.len() as u64) >> 32) as usize]);
+        }
+        
+        let mut weights = vec![0u8; 48];
+        for (i, weight) in weights.iter_mut().enumerate() {
+            if Unlockable::from_u8(i as u8).is_some_and(|plant| pool.contains(&plant)) {
+                *weight = 1 + (rng.next_u32() % 4) as u8;
+            }
+        }
+        
+        (weights, pool)
+    }
+    
+    fn randomise_flag_counts(seed: u64, enabled: bool) -> Vec<Option<u8>> { //indexed by level, the first level is never changed
+        let mut rng = ChaCha8Rng::seed_from_u64(seed ^ hash_str("flag_counts"));
+        let level_data = LEVEL_DATA.get().unwrap();
+        let mut ret: Vec<Option<u8>> = level_data.iter().map(|level| level.flags).collect();
+        if enabled {
+            for (flags, level) in ret.iter_mut().zip(level_data).skip(1) {
+                let Some(flags) = flags else {
+                    continue;
+                };
+                let min_flags = if level.level_type == LevelType::Roof && *flags > 1 {2} else {1}; //only the roof levels that start with one flag have pots placed
+                *flags = (*flags + (rng.next_u32() % 3) as u8).saturating_sub(1).clamp(min_flags, 4); //at most one flag away from the original
+            }
+        }
+        ret
+    }
+    
+    fn randomise_scenes(seed: u64, enabled: bool) -> Vec<LevelType> { //indexed by level, the first level and conveyor levels are never changed
+        let mut rng = ChaCha8Rng::seed_from_u64(seed ^ hash_str("scenes"));
+        let level_data = LEVEL_DATA.get().unwrap();
+        let mut ret: Vec<LevelType> = level_data.iter().map(|level| level.level_type).collect();
+        if enabled {
+            for (scene, _) in ret.iter_mut().zip(level_data).skip(1).filter(|(_, level)| level.conveyor_plants.is_none()) {
+                *scene = LevelType::ALL[(rng.next_u32() % LevelType::ALL.len() as u32) as usize];
+            }
+        }
+        ret
+    }
+    
+    fn randomise_zombie_stats(seed: u64, enabled: bool, params: &DifficultyParams) -> Vec<Vec<u8>> { //indexed by level, the first level is never changed
+        if !enabled {
+            return vec![Vec::new(); 45];
+        }
+        let mut rng = ChaCha8Rng::seed_from_u64(seed ^ hash_str("zombie_stats"));
+        let zombie_count = ZOMBIE_DATA.get().unwrap().len();
+        let mut ret = vec![vec![0x80; ZOMBIE_STAT_STRIDE * 3]; 45];
+        for stats in ret.iter_mut().skip(1) {
+            for stat in stats.chunks_exact_mut(ZOMBIE_STAT_STRIDE) {
+                for byte in stat.iter_mut().take(zombie_count) {
+                    *byte = params.scale_mul((Self::weight_curve(rng.next_u32()) * 127.5 + 127.5).round() as u8);
+                }
+            }
+            stats[0] = stats[0].min(0xBF); //the basic zombie always has to be able to spawn, so it is never buffed into the next health tier
+        }
+        ret
+    }
+    
+    #[allow(clippy::too_many_arguments)]
+    fn randomise_plant_stat( //indexed by level, the first level is never changed
+        seed: u64,
+        stat: &str,
+        enabled: bool,
+        protected: Unlockable, //never made worse than normal, like the sunflower's cost and cooldown
+        enum_variants: &FxHashMap<String, u64>,
+        fuse_data: &FxHashMap<u32,[u32;2]>,
+        params: &DifficultyParams,
+    ) -> Vec<Vec<u8>> {
+        if !enabled {
+            return vec![Vec::new(); 45];
+        }
+        let (plant_map, plant_ids, _) = Self::get_plant_map_and_ids(enum_variants);
+        let protected_idx = plant_map.get(&format!("{protected:?}")).map(|idx| *idx as usize);
+        let mut rng = ChaCha8Rng::seed_from_u64(seed ^ hash_str(stat));
+        let mut ret = vec![vec![0x80; plant_ids.len()]; 45];
+        for stats in ret.iter_mut().skip(1) {
+            for byte in stats.iter_mut() {
+                *byte = params.scale_mul((rng.next_u32() >> 24) as u8);
+            }
+            if let Some(idx) = protected_idx {
+                stats[idx] = stats[idx].max(0x80);
+            }
+            Self::set_fusion_firerates(stats, &plant_ids, fuse_data);
+        }
+        ret
+    }
+    
+    pub fn randomise_fusions(seed: u64, enabled: bool, fuse_data: &FxHashMap<u32,[u32;2]>) -> Option<FxHashMap<u32,[u32;2]>> {
+        if !enabled {
+            return None;
+        }
+        //only recipes made from two base plants are shuffled, so fusions of fusions can never need themselves
+        let mut fusions: Vec<u32> = fuse_data.iter()
+            .filter(|(_, plants)| plants.iter().all(|plant| !fuse_data.contains_key(plant)))
+            .map(|(fusion, _)| *fusion)
+            .collect();
+        fusions.sort_unstable();
+        
+        let mut rng = ChaCha8Rng::seed_from_u64(seed ^ hash_str("fusions"));
+        let mut recipes: Vec<([u32;2], u32)> = fusions.iter().map(|fusion| (fuse_data[fusion], rng.next_u32())).collect();
+        recipes.sort_by_key(|(_recipe, val)| *val);
+        
+        let mut ret = fuse_data.clone();
+        for (fusion, (recipe, _val)) in fusions.into_iter().zip(recipes) {
+            ret.insert(fusion, recipe);
+        }
+        Some(ret)
+    }
+    
+    fn plant_pick_weight(plant: Unlockable, unlocked: &FxHashSet<Unlockable>, progress: f32) -> f32 {
+        let data = &PLANT_DATA[plant as usize];
+        let usefulness = RULES.get().unwrap().plant_usefulness[plant as usize];
+        let cost_mul = (progress + 150. / data.cost.max(150) as f32).min(1.); //expensive plants are hard to use early on
+        let same_role = unlocked.iter().filter(|unlocked| PLANT_DATA[**unlocked as usize].role == data.role).count();
+        usefulness * cost_mul * 2. / (2. + same_role as f32) //spreads the roles out
+    }
+    
+    fn weighted_plant_pick(plant_choices: &[(usize, Unlockable)], unlocked: &FxHashSet<Unlockable>, progress: f32, rand: u32) -> usize {
+        let weights: Vec<f32> = plant_choices.iter().map(|(_, plant)| Self::plant_pick_weight(*plant, unlocked, progress)).collect();
+        let mut target = rand as f32 / (u32::MAX as f32 + 1.) * weights.iter().sum::<f32>();
+        for (i, weight) in weights.iter().enumerate() {
+            if target < *weight {
+                return i;
+            }
+            target -= weight;
+        }
+        weights.len() - 1
+    }
+    
+    pub fn zombie_flags(&self, level: usize, zombie_idx: usize) -> ZombieFlags { //includes the health tier a zombie was buffed into
+        let mut flags = ZOMBIE_DATA.get().unwrap()[zombie_idx].flags;
+        let Some(stats) = self.restrictions_data.as_ref().and_then(|restrictions_data| restrictions_data.zombie_stats.get(level - 1)) else {
+            return flags;
+        };
+        if stats.get(zombie_idx).is_some_and(|health| mul_from_u8(*health) >= BUFFED_HEALTH) {
+            if flags.contains(ZombieFlags::HIGH_HEALTH) {
+                flags |= ZombieFlags::V_HIGH_HEALTH;
+            } else {
+                flags |= ZombieFlags::HIGH_HEALTH;
+            }
+        }
+        flags
+    }
+    
+    pub fn scene(&self, level: usize) -> LevelType {
+        if let Some(restrictions_data) = &self.restrictions_data {
+            return restrictions_data.scenes[level - 1];
+        }
+        if let (Some(scenes), Some(slot)) = (&self.scenes, self.level_order.iter().position(|x| *x as usize == level)) {
+            if let Some(scene) = scenes[slot].first().copied().and_then(LevelType::from_u8) {
+                return scene;
+            }
+        }
+        LEVEL_DATA.get().unwrap()[level - 1].level_type
+    }
+    
+    pub fn flag_count(&self, level: usize) -> Result<u8, GenerationError> {
+        if let Some(flags) = self.restrictions_data.as_ref().and_then(|restrictions_data| restrictions_data.flag_counts.get(&(level as u8))) {
+            return Ok(*flags);
+        }
+        if let (Some(flag_counts), Some(slot)) = (&self.flag_counts, self.level_order.iter().position(|x| *x as usize == level)) {
+            if let Some(flags) = flag_counts[slot].first().filter(|flags| **flags != 0) {
+                return Ok(*flags);
+            }
+        }
+        LEVEL_DATA.get().unwrap()[level - 1].flags.ok_or(GenerationError::NoFlags(level))
+    }
+    
     fn randomise_sounds(seed: u64) -> Vec<u64> {
         let mut rng = ChaCha8Rng::seed_from_u64(seed.wrapping_add(hash_str("random_sounds")));
         let mut ret = Vec::with_capacity(45);
@@ -510,18 +956,15 @@ impl RandomisationData {
         ret
     }
     
-    fn get_plant_map_and_ids(meta: &IL2CppDumper) -> (FxHashMap<String, u32>, Vec<u32>, FxHashMap<u32, u32>) {
-        let mut enum_variants: FxHashMap<String, u64> = HashMap::with_capacity_and_hasher(16384, BuildHasherDefault::default());
+    pub fn get_plant_map_and_ids(enum_variants: &FxHashMap<String, u64>) -> (FxHashMap<String, u32>, Vec<u32>, FxHashMap<u32, u32>) {
         let mut plant_map: FxHashMap<String, u32> = HashMap::with_capacity_and_hasher(384, BuildHasherDefault::default());
         let mut rev_map: FxHashMap<u32, u32> = HashMap::with_capacity_and_hasher(384, BuildHasherDefault::default());
         let mut plant_ids: Vec<u32> = Vec::with_capacity(384);
         
-        meta.get_enum_variants(&mut enum_variants);
-        
         for (name, val) in enum_variants {
-            if name.starts_with("PlantType::") && val as i64 >= 0 {
-                plant_ids.push(val as u32);
-                plant_map.insert(name.strip_prefix("PlantType::").unwrap().to_owned(), val as u32);
+            if name.starts_with("PlantType::") && *val as i64 >= 0 {
+                plant_ids.push(*val as u32);
+                plant_map.insert(name.strip_prefix("PlantType::").unwrap().to_owned(), *val as u32);
             }
         }
         
@@ -538,276 +981,103 @@ impl RandomisationData {
         (plant_map, plant_ids, rev_map)
     }
     
-    fn randomise_plant_attrs(&mut self, meta: &IL2CppDumper, fuse_data: &FxHashMap<u32,[u32;2]>, seed: u64) {
-        let (plant_map, plant_ids, rev_map) = Self::get_plant_map_and_ids(meta);
+    fn randomise_level_spawns( //also returns whether any odyssey zombies can spawn
+        seed: u64,
+        level: usize,
+        scene: LevelType,
+        blacklisted: bool,
+        weights_rng: &mut ChaCha8Rng,
+        params: &DifficultyParams,
+    ) -> (Vec<(u32, u32)>, bool) {
+        let zombie_data = ZOMBIE_DATA.get().unwrap();
+        let mut has_odyssey = false;
+        let mut vec = Vec::new();
+        let mut bitfield = Self::randomise_spawns_no_restrictions(seed, level, scene, blacklisted);
+        for (byte_idx, byte) in bitfield.iter_mut().enumerate() {
+            loop {
+                let bit_pos = byte.trailing_zeros();
+                if bit_pos == 8 {
+                    break;
+                }
+                *byte ^= 1 << bit_pos;
+                let idx = bit_pos as usize + byte_idx * 8;
+                let mut weight_mul = 10f64.powf(Self::weight_curve(weights_rng.next_u32()) * params.weight_spread);
+                if idx == 0 {
+                    weight_mul = weight_mul.max(1.0);
+                }
+                vec.push((idx as u32, (weight_mul * zombie_data[idx].default_weight as f64).round() as u32));
+                if zombie_data[idx].flags.contains(ZombieFlags::IS_ODYSSEY) {
+                    has_odyssey = true;
+                }
+            }
+        }
+        (vec, has_odyssey)
+    }
+    
+    fn randomise_plant_attrs(&mut self, enum_variants: &FxHashMap<String, u64>, fuse_data: &FxHashMap<u32,[u32;2]>, seed: u64, params: &DifficultyParams) {
+        let (plant_map, plant_ids, rev_map) = Self::get_plant_map_and_ids(enum_variants);
+        let non_fused_ids = Self::non_fused_ids(&plant_ids, fuse_data);
+        let mut rngs = PlantAttrRngs::new(seed);
         
-        let mut cost_rng      = ChaCha8Rng::seed_from_u64(seed ^ hash_str("plant_cost"));
-        let mut cooldowns_rng = ChaCha8Rng::seed_from_u64(seed ^ hash_str("plant_cooldowns"));
-        let mut firerates_rng = ChaCha8Rng::seed_from_u64(seed ^ hash_str("plant_firerates"));
-        let restrictions_data = self.restrictions_data.as_mut().unwrap();
+        for level_idx in 2..=45 {
+            let plants = self.randomise_level_plants(&mut rngs, &plant_ids, &rev_map, &non_fused_ids, fuse_data, params);
+            self.restrictions_data.as_mut().unwrap().level_plants.insert(level_idx, plants);
+        }
         
+        self.restrictions_data.as_mut().unwrap().plant_map = plant_map;
+    }
+    
+    fn non_fused_ids(plant_ids: &[u32], fuse_data: &FxHashMap<u32,[u32;2]>) -> FxHashSet<u32> {
         let mut non_fused_ids: FxHashSet<u32> = plant_ids.iter().copied().collect();
         for k in fuse_data.keys() {
             non_fused_ids.remove(k);
         }
-        
-        for level_idx in 2..=45 {
-            let mut cooldowns: Vec<(u32, u8)> = Vec::with_capacity(48);
-            let mut costs:     Vec<(u32, u8)> = Vec::with_capacity(48);
-            let mut firerates: Vec<u8> = vec![0; plant_ids.len()];
-            for i in 0..48 {
-                let byte = (Self::weight_curve(i * 0x572_620A) * 127.5 + 127.5).round() as u8; //0x572_620A is 2^32 / 47
-                cooldowns.push((cooldowns_rng.next_u32(), if self.cooldowns.is_some() {byte} else {0x80}));
-                costs.push((cost_rng.next_u32(), if self.costs.is_some() {byte} else {0x80}));
-            }
-            
-            for i in non_fused_ids.iter() {
-                firerates[*rev_map.get(i).unwrap() as usize] = (if self.firerates.is_some() {firerates_rng.next_u32() >> 24} else {0x80}) as u8;
-            }
-            
-            cooldowns.sort_by_key(|(key, _)| *key);
-            costs.sort_by_key(|(key, _)| *key);
-            
-            let mut menu: Vec<(u8, u8)> = cooldowns
-                .iter()
-                .zip(costs.iter())
-                .map(|((_, cd), (_, cs))| (*cd, *cs))
-                .collect();
-            
-            menu[1].0 = menu[1].0.min(0x80);
-            menu[1].1 = menu[1].1.min(0x80);
-            
-            Self::set_fusion_firerates(&mut firerates, &plant_ids, fuse_data);
-            
-            restrictions_data.level_plants.insert(level_idx, LevelPlants {
-                menu,
-                all: firerates,
-            });
+        non_fused_ids
+    }
+    
+    fn randomise_level_plants(
+        &self,
+        rngs: &mut PlantAttrRngs,
+        plant_ids: &[u32],
+        rev_map: &FxHashMap<u32, u32>,
+        non_fused_ids: &FxHashSet<u32>,
+        fuse_data: &FxHashMap<u32,[u32;2]>,
+        params: &DifficultyParams,
+    ) -> LevelPlants {
+        let mut cooldowns: Vec<(u32, u8)> = Vec::with_capacity(48);
+        let mut costs:     Vec<(u32, u8)> = Vec::with_capacity(48);
+        let mut firerates: Vec<u8> = vec![0; plant_ids.len()];
+        for i in 0..48 {
+            let byte = params.scale_mul((Self::weight_curve(i * 0x572_620A) * 127.5 + 127.5).round() as u8); //0x572_620A is 2^32 / 47
+            cooldowns.push((rngs.cooldowns.next_u32(), if self.cooldowns.is_some() {byte} else {0x80}));
+            costs.push((rngs.cost.next_u32(), if self.costs.is_some() {byte} else {0x80}));
+        }
+        
+        for i in non_fused_ids.iter() {
+            firerates[*rev_map.get(i).unwrap() as usize] = if self.firerates.is_some() {params.scale_mul((rngs.firerates.next_u32() >> 24) as u8)} else {0x80};
+        }
+        
+        cooldowns.sort_by_key(|(key, _)| *key);
+        costs.sort_by_key(|(key, _)| *key);
+        
+        let mut menu: Vec<(u8, u8)> = cooldowns
+            .iter()
+            .zip(costs.iter())
+            .map(|((_, cd), (_, cs))| (*cd, *cs))
+            .collect();
+        
+        menu[1].0 = menu[1].0.min(0x80);
+        menu[1].1 = menu[1].1.min(0x80);
+        
+        Self::set_fusion_firerates(&mut firerates, plant_ids, fuse_data);
+        
+        LevelPlants {
+            menu,
+            all: firerates,
         }
-        
-        restrictions_data.plant_map = plant_map;
-    }
-    
-    fn get_solutions_1() -> FxHashMap<Problem, Solutions> {
-        [
-            (Problem::Water1, vec![
-                vec![
-                    Unlockable::Tanglekelp,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::SeaShroom,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::LilyPad,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::ThreePeater,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::StarFruit,
-                ].into_boxed_slice(),
-                vec![ //gloom should be safe on a 1 flag as long as you can get 2 up quickly
-                    Unlockable::FumeShroom,
-                    Unlockable::EndoFlame,
-                ].into_boxed_slice(),
-                vec![ //same here
-                    Unlockable::FumeShroom,
-                    Unlockable::GloomShroom,
-                ].into_boxed_slice(),
-                //vec![ //present cooldown is too long these days for this to be practical
-                //    Unlockable::Present,
-                //].into_boxed_slice(),
-            ].into_boxed_slice()),
-            (Problem::Roof, vec![
-                vec![
-                    Unlockable::Pot,
-                ].into_boxed_slice(),
-            ].into_boxed_slice()),
-            (Problem::HighHealth, vec![
-                vec![
-                    Unlockable::HypnoShroom,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::Chomper,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::Squash,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::CherryBomb,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::Jalapeno,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::TorchWood,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::Plantern,
-                    Unlockable::StarFruit,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::Melonpult,
-                ].into_boxed_slice(),
-                //vec![ //doom cooldown is too long for this to be practical
-                //    Unlockable::DoomShroom,
-                //].into_boxed_slice(),
-            ].into_boxed_slice()),
-            (Problem::Balloon, vec![
-                vec![
-                    Unlockable::Cactus,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::LilyPad, //cattail
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::Blover,
-                ].into_boxed_slice(),
-            ].into_boxed_slice()),
-            (Problem::NoPuff, vec![
-                vec![
-                    Unlockable::SmallPuff,
-                ].into_boxed_slice(),
-            ].into_boxed_slice()),
-        ].into_iter().collect()
-    }
-    
-    fn get_solutions_2() -> FxHashMap<Problem, Solutions> {
-        [
-            (Problem::Water2, vec![
-                vec![
-                    Unlockable::LilyPad,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::ThreePeater,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::StarFruit,
-                ].into_boxed_slice(),
-            ].into_boxed_slice()),
-            (Problem::ReallyHighHealth, vec![
-                vec![
-                    Unlockable::Chomper,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::HypnoShroom,
-                ].into_boxed_slice(),
-            ].into_boxed_slice()),
-            (Problem::BalloonWater, vec![
-                vec![
-                    Unlockable::Cactus, //this is just here so that this option can have increased weight if cactus is selected
-                    Unlockable::LilyPad,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::Cactus,
-                    Unlockable::SeaShroom,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::Cactus,
-                    Unlockable::StarFruit,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::LilyPad, //cattail
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::Blover,
-                ].into_boxed_slice(),
-            ].into_boxed_slice()),
-            (Problem::Snorkle, vec![
-                vec![
-                    Unlockable::LilyPad,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::Tanglekelp,
-                    Unlockable::Squash,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::Tanglekelp,
-                    Unlockable::Jalapeno,
-                ].into_boxed_slice(),
-            ].into_boxed_slice()),
-        ].into_iter().collect()
-    }
-    
-    fn get_solutions_3() -> FxHashMap<Problem, Solutions> {
-        [
-            (Problem::Water34, vec![
-                vec![
-                    Unlockable::LilyPad,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::ThreePeater,
-                    Unlockable::Tanglekelp,
-                    Unlockable::TorchWood,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::Cornpult,
-                    Unlockable::SeaShroom, //earlygame because cob cannon is bad in earlygame, plus so you can put sun on water
-                    Unlockable::EndoFlame,
-                ].into_boxed_slice(),
-            ].into_boxed_slice()),
-            (Problem::DarkMicheal, vec![
-                vec![
-                    Unlockable::WallNut,
-                    Unlockable::TallNut,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::WallNut,
-                    Unlockable::EndoFlame,
-                ].into_boxed_slice(),
-            ].into_boxed_slice()),
-            (Problem::Kirov, vec![
-                vec![
-                    Unlockable::Cactus,
-                    Unlockable::Plantern,
-                    Unlockable::LilyPad,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::Cactus,
-                    Unlockable::StarFruit,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::LilyPad,
-                    Unlockable::CattailPlant, //we include cattail/endoflame here because you probably want a lot of them to counter kirov
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::LilyPad,
-                    Unlockable::EndoFlame, //also I am aware this can't counter land balloons, too bad!
-                ].into_boxed_slice(),
-            ].into_boxed_slice()),
-            (Problem::Gargantaur, vec![
-                vec![
-                    Unlockable::CherryBomb,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::TorchWood,
-                    Unlockable::ThreePeater,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::TorchWood,
-                    Unlockable::Jalapeno,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::Cornpult,
-                    Unlockable::EndoFlame,
-                    Unlockable::Jalapeno,
-                ].into_boxed_slice(),
-                vec![
-                    Unlockable::StarFruit,
-                    Unlockable::Plantern,
-                ].into_boxed_slice(),
-            ].into_boxed_slice()),
-        ].into_iter().collect()
-    }
-    
-    fn get_solutions_all() -> FxHashMap<Problem, Solutions> {
-        let iter = Self::get_solutions_1().into_iter()
-            .chain(Self::get_solutions_2())
-            .chain(Self::get_solutions_3());
-        iter.collect()
-    }
-    
-    fn compute_zombie_freq_data_bytes(spawns: &[u8], weights: &[u8], level: usize) -> Option<FrequencyData> {
+    }
+    
+    fn compute_zombie_freq_data_bytes(spawns: &[u8], weights: &[u8], level: usize, flags: Option<u8>) -> Result<FrequencyData, GenerationError> {
         let zombie_data = ZOMBIE_DATA.get().unwrap();
         
         let mut spawn_vec: Vec<(u32, u32, u32)> = Vec::with_capacity(weights.len() >> 2);
@@ -819,12 +1089,11 @@ impl RandomisationData {
         
         spawn_vec.sort_by_key(|(_, _, points)| *points);
         
-        Self::compute_zombie_freq_data(&spawn_vec, level)
+        Self::compute_zombie_freq_data(&spawn_vec, level, flags.ok_or(GenerationError::NoFlags(level))?, None)
     }
     
-    fn compute_zombie_freq_data(spawn_vec: &[(u32, u32, u32)], level: usize) -> Option<FrequencyData> {
+    fn compute_zombie_freq_data(spawn_vec: &[(u32, u32, u32)], level: usize, flags: u8, reuse: Option<&ExpectedCounts>) -> Result<FrequencyData, GenerationError> {
         let zombie_data = ZOMBIE_DATA.get().unwrap();
-        let level_data = LEVEL_DATA.get().unwrap();
         
         let mut spawn_vec_pre_10 = spawn_vec.to_vec();
         let mut pre_10_map = Vec::from_iter(0..spawn_vec.len());
@@ -836,60 +1105,17 @@ impl RandomisationData {
             }
         }
         
-        fn compute_freq_for_wave(spawn_vec: &[(u32, u32, u32)], wave: isize) -> Vec<f64> {
-            let mut wavepoints_max = 0;
-            
-            for (_, _, points) in spawn_vec {
-                wavepoints_max = isize::max(*points as isize, wavepoints_max);
-            }
-            
-            assert_ne!(wavepoints_max, 0);
-            
-            let mut spawns_lut: Vec<Vec<f64>> = Vec::with_capacity(wavepoints_max as usize - 1);
-            
-            for i in 1 ..= wavepoints_max {
-                let mut sum = 0f64;
-                let mut vec: Vec<f64> = Vec::new();
-                for (_, weight, _) in spawn_vec.iter().take_while(|(_, _, points)| *points as isize <= i) {
-                    sum += *weight as f64;
-                    vec.push(*weight as f64);
-                }
-                
-                for weight in vec.iter_mut() {
-                    *weight /= sum;
-                }
-                spawns_lut.push(vec);
-            }
-            
-            let total_wavepoints = (wave as usize * 5 / 3) * if wave % 10 == 0 {2} else {1};
-            let mut zombie_odds: Vec<f64> = vec![0f64; spawn_vec.len()];
-            let mut wavepoint_odds_array = vec![0f64; total_wavepoints];
-            wavepoint_odds_array[total_wavepoints - 1] = 1f64;
-            
-            for remaining_points in (1..=total_wavepoints).rev() {
-                let chance_1 = wavepoint_odds_array[remaining_points - 1];
-                for (chance_2, ((_, _, choice_wavepoints), choice_odds)) in
-                    spawns_lut[usize::min(remaining_points, spawns_lut.len()) - 1]
-                    .iter()
-                    .zip(spawn_vec.iter().zip(zombie_odds.iter_mut()))
-                {
-                    let chance = chance_1 * *chance_2;
-                    *choice_odds += chance;
-                    if remaining_points > *choice_wavepoints as usize {
-                        wavepoint_odds_array[remaining_points - *choice_wavepoints as usize - 1] += chance;
-                    }
-                }
-            }
-            
-            zombie_odds
-        }
-        
-        let wave_max = level_data[level - 1].flags? as isize * 10;
+        let wave_max = flags as isize * 10;
+        let pre_10_counts = expected_counts_table(&spawn_vec_pre_10, wave_budget(9), reuse.and_then(|reuse| Some((&reuse.spawn_vec_pre_10[..], reuse.pre_10.as_deref()?))));
+        let counts = expected_counts_table(spawn_vec, wave_budget(wave_max), reuse.and_then(|reuse| Some((&reuse.spawn_vec[..], reuse.all.as_deref()?))));
         let mut freq_array = vec![f32::NAN; wave_max as usize * spawn_vec.len()];
         
         for wave in 1 ..= wave_max {
-            let spawn_data = if wave < 10 {&spawn_vec_pre_10} else {spawn_vec};
-            let zombie_freq = compute_freq_for_wave(spawn_data, wave);
+            let zombie_freq = if wave < 10 {
+                pre_10_counts.as_ref().map(|counts| &counts[wave_budget(wave) * spawn_vec_pre_10.len()..][..spawn_vec_pre_10.len()])
+            } else {
+                counts.as_ref().map(|counts| &counts[wave_budget(wave) * spawn_vec.len()..][..spawn_vec.len()])
+            }.ok_or(GenerationError::NoZombies(level))?;
             if wave < 10 {
                 let off = spawn_vec.len() * (wave as usize - 1);
                 for dst in freq_array
@@ -942,7 +1168,7 @@ impl RandomisationData {
                     first_wave = j + 1;
                 }
             }
-            first_wave = u32::max(first_wave, wave_max as u32);
+            first_wave = u32::min(first_wave, wave_max as u32); //zombies that never show up count as showing up on the last wave
             max_frequency.insert(*id, (max_freq, max_wave));
             first_flag_totals.insert(*id, total_freq_ff);
             first_2_flag_max.insert(*id, max_freq_f2f);
@@ -952,17 +1178,42 @@ impl RandomisationData {
             }
         }
         
-        Some(FrequencyData {
+        Ok(FrequencyData {
             raw_averages: freq_array,
             max_frequency,
             first_flag_totals,
             first_2_flag_max,
             first_wave_occurence_avgs,
             totals,
+            counts: Arc::new(ExpectedCounts {
+                spawn_vec_pre_10,
+                pre_10: pre_10_counts,
+                spawn_vec: spawn_vec.to_vec(),
+                all: counts,
+            }),
         })
     }
     
-    pub fn compute_zombie_freq_data_cached(&mut self, level_spawns: &[(u32,u32)], level: usize) -> Option<FrequencyData> {
+    pub fn compute_zombie_freq_data_cached(&mut self, level_spawns: &[(u32,u32)], level: usize) -> Result<FrequencyData, GenerationError> {
+        let mut new_entries = HashMap::default();
+        let ret = self.frequency_data(level_spawns, level, None, &mut new_entries);
+        self.cache_frequency_data(new_entries);
+        ret
+    }
+    
+    fn cache_frequency_data(&mut self, new_entries: FxHashMap<FrequencyCacheKey, FrequencyData>) {
+        if let Some(restrictions_data) = self.restrictions_data.as_mut() {
+            restrictions_data.frequency_cache.extend(new_entries);
+        }
+    }
+    
+    fn frequency_data( //only reads the cache, so checks can run in parallel and merge what they computed afterwards
+        &self,
+        level_spawns: &[(u32,u32)],
+        level: usize,
+        reuse: Option<&FrequencyData>, //frequencies of the same level with other weights
+        new_entries: &mut FxHashMap<FrequencyCacheKey, FrequencyData>,
+    ) -> Result<FrequencyData, GenerationError> {
         let key = FrequencyCacheKey {
             spawns: level_spawns.into(),
             level,
@@ -976,36 +1227,29 @@ impl RandomisationData {
         }
         
         spawn_vec.sort_by_key(|(_, _, points)| *points);
+        let flags = self.flag_count(level)?;
         
-        if let Some(restrictions_data) = &mut self.restrictions_data.as_mut() {
-            let frequency_cache = &mut restrictions_data.frequency_cache;
-            if let Some(entry) = frequency_cache.get(&key) {
-                return Some(entry.clone());
+        if let Some(restrictions_data) = self.restrictions_data.as_ref() {
+            if let Some(entry) = restrictions_data.frequency_cache.get(&key).or_else(|| new_entries.get(&key)) {
+                return Ok(entry.clone());
             }
             
-            if let Some(freq_data) = Self::compute_zombie_freq_data(&spawn_vec, level) {
-                frequency_cache.insert(key, freq_data.clone());
-                Some(freq_data)
-            } else {
-                None
-            }
+            let freq_data = Self::compute_zombie_freq_data(&spawn_vec, level, flags, reuse.map(|reuse| &*reuse.counts))?;
+            new_entries.insert(key, freq_data.clone());
+            Ok(freq_data)
         } else {
-            Self::compute_zombie_freq_data(&spawn_vec, level)
+            Self::compute_zombie_freq_data(&spawn_vec, level, flags, reuse.map(|reuse| &*reuse.counts))
         }
     }
     
     fn is_any_solution_satisfied(
-        &mut self,
+        &self,
         solutions: &Solutions,
-        level: &LevelData,
+        level_idx: u32,
         used_solutions: &mut FxHashMap<Solutions, u32>,
         importance: u32
     ) -> bool {
-        let unlocked_plants = if let Some(conveyor_plants) = &level.conveyor_plants {
-            conveyor_plants
-        } else {
-            &self.restrictions_data.as_ref().unwrap().unlocked_plants
-        };
+        let unlocked_plants = self.level_plants(level_idx);
         
         let mut vec: Vec<Box<[Unlockable]>> = Vec::with_capacity(12); //vec is necessary to prevent mutable + immutable borrow
         let mut solution_found = false;
@@ -1027,627 +1271,27 @@ impl RandomisationData {
         solution_found
     }
     
-    #[allow(dead_code)]
-    fn get_zombie_solutions(&self, freq_data: &FrequencyData, zombie_idx: u32) -> SmallVec<[ProblemData; 1]> {
+    fn level_plants(&self, level_idx: u32) -> &FxHashSet<Unlockable> { //the plants usable on a level, conveyor levels have their own
+        let restrictions_data = self.restrictions_data.as_ref().unwrap();
+        if let Some((_, pool)) = restrictions_data.conveyor_pools.get(&(level_idx as u8)) {
+            pool
+        } else if let Some(conveyor_plants) = &LEVEL_DATA.get().unwrap()[level_idx as usize - 1].conveyor_plants {
+            conveyor_plants
+        } else {
+            &restrictions_data.unlocked_plants
+        }
+    }
+    
+    fn get_zombie_solutions(&self, freq_data: &FrequencyData, level_idx: u32, zombie_idx: u32) -> SmallVec<[ProblemData; 1]> {
         let mut out_vec = SmallVec::new();
-        static SOLUTION_TABLE: OnceLock<FxHashMap<ZombieFlags, Box<[SolutionEntry]>>> = OnceLock::new();
-        static ZOMBIE_SOLUTION_TABLE: OnceLock<FxHashMap<ZombieType, Box<[SolutionEntry]>>> = OnceLock::new();
-        let solution_table = &SOLUTION_TABLE.get_or_init(|| {
-            let mut solution_table: FxHashMap<ZombieFlags, Box<[SolutionEntry]>> = [
-                (ZombieFlags::HIGH_HEALTH, vec![
-                    SolutionEntry {
-                        plants: [Unlockable::Squash].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::Jalapeno].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::CherryBomb].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::HypnoShroom].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::HypnoShroom,
-                            Unlockable::SmallPuff,
-                        ].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::Chomper].into_iter().collect(),
-                        uses: Some(2),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::DoomShroom].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::DoomShroom,
-                            Unlockable::SmallPuff,
-                        ].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::CherryBomb,
-                            Unlockable::WallNut,
-                        ].into_iter().collect(),
-                        uses: Some(2),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::WallNut,
-                            Unlockable::WallNut,
-                        ].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::WallNut].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::Pumpkin].into_iter().collect(),
-                        ..Default::default()
-                    },
-                ].into_boxed_slice()),
-                (ZombieFlags::EVIL_DEATH, vec![
-                    SolutionEntry {
-                        plants: [Unlockable::Squash].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::Jalapeno].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::CherryBomb].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::HypnoShroom].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::HypnoShroom,
-                            Unlockable::SmallPuff,
-                        ].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::Chomper].into_iter().collect(),
-                        uses: Some(2),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::DoomShroom].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::DoomShroom,
-                            Unlockable::SmallPuff,
-                        ].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::CherryBomb,
-                            Unlockable::WallNut,
-                        ].into_iter().collect(),
-                        uses: Some(2),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::WallNut,
-                            Unlockable::WallNut,
-                        ].into_iter().collect(),
-                        ..Default::default()
-                    },
-                ].into_boxed_slice()),
-                (ZombieFlags::V_HIGH_HEALTH, vec![
-                    SolutionEntry {
-                        plants: [Unlockable::HypnoShroom].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::HypnoShroom,
-                            Unlockable::SmallPuff,
-                        ].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::Chomper].into_iter().collect(),
-                        uses: Some(2),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::WallNut,
-                            Unlockable::WallNut,
-                        ].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::WallNut].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::Pumpkin].into_iter().collect(),
-                        ..Default::default()
-                    },
-                ].into_boxed_slice()),
-                (ZombieFlags::FLIES, vec![
-                    !SolutionEntry {
-                        plants: [Unlockable::Squash].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::Jalapeno].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::CherryBomb].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::DoomShroom].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    !SolutionEntry {
-                        plants: [
-                            Unlockable::DoomShroom,
-                            Unlockable::SmallPuff,
-                        ].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    !SolutionEntry {
-                        plants: [Unlockable::HypnoShroom].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    !SolutionEntry {
-                        plants: [
-                            Unlockable::HypnoShroom,
-                            Unlockable::SmallPuff,
-                        ].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    !SolutionEntry {
-                        plants: [Unlockable::Chomper].into_iter().collect(),
-                        uses: Some(2),
-                        ..Default::default()
-                    },
-                    !SolutionEntry {
-                        plants: [
-                            Unlockable::WallNut,
-                            Unlockable::WallNut,
-                        ].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    !SolutionEntry {
-                        plants: [Unlockable::WallNut].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    !SolutionEntry {
-                        plants: [Unlockable::Pumpkin].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::CherryBomb,
-                            Unlockable::WallNut,
-                        ].into_iter().collect(),
-                        uses: Some(2),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::Cactus,
-                            Unlockable::StarFruit,
-                        ].into_iter().collect(),
-                        uses: None,
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::Cactus,
-                            Unlockable::Plantern,
-                        ].into_iter().collect(),
-                        uses: None,
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::Cactus,
-                            Unlockable::DoomShroom,
-                        ].into_iter().collect(),
-                        uses: None,
-                        ..Default::default()
-                    },
-                ].into_boxed_slice()),
-                (ZombieFlags::GARG_TYPE, vec![
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::WallNut,
-                            Unlockable::WallNut,
-                        ].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::Squash].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::Jalapeno].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::CherryBomb].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::Chomper].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::DoomShroom].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::DoomShroom,
-                            Unlockable::SmallPuff,
-                        ].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::CherryBomb,
-                            Unlockable::WallNut,
-                        ].into_iter().collect(),
-                        uses: Some(2),
-                        ..Default::default()
-                    },
-                ].into_boxed_slice()),
-                (ZombieFlags::IS_VEHICLE, vec![
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::Caltrop,
-                            Unlockable::WallNut,
-                        ].into_iter().collect(),
-                        uses: Some(4),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::Caltrop].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::Caltrop,
-                            Unlockable::ThreePeater,
-                        ].into_iter().collect(),
-                        uses: None,
-                        ..Default::default()
-                    },
-                ].into_boxed_slice()),
-                (ZombieFlags::IS_METAL, vec![
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::Magnetshroom,
-                            Unlockable::Blover,
-                        ].into_iter().collect(),
-                        uses: Some(4),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::Magnetshroom].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::Magnetshroom,
-                            Unlockable::Plantern,
-                        ].into_iter().collect(),
-                        uses: None,
-                        ..Default::default()
-                    },
-                ].into_boxed_slice()),
-                (ZombieFlags::DOES_NOT_EAT, vec![
-                    !SolutionEntry {
-                        plants: [
-                            Unlockable::HypnoShroom,
-                            Unlockable::SmallPuff,
-                        ].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    !SolutionEntry {
-                        plants: [Unlockable::HypnoShroom].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    !SolutionEntry {
-                        plants: [Unlockable::WallNut].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    !SolutionEntry {
-                        plants: [Unlockable::Pumpkin].into_iter().collect(),
-                        ..Default::default()
-                    },
-                ].into_boxed_slice()),
-                (ZombieFlags::IS_WATER, vec![
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::Tanglekelp,
-                            Unlockable::ThreePeater,
-                        ].into_iter().collect(),
-                        weight: 2.,
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [Unlockable::Tanglekelp].into_iter().collect(),
-                        weight: 2.,
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::Tanglekelp,
-                            Unlockable::Squash,
-                        ].into_iter().collect(),
-                        weight: 2.,
-                        uses: Some(3),
-                        ..Default::default()
-                    },
-                ].into_boxed_slice()),
-            ].into_iter().collect();
-            
-            for val in solution_table.values_mut() {
-                for solution in val {
-                    solution.plants.sort_unstable_by_key(|x| *x as isize);
-                }
-            }
-            
-            solution_table
-        });
-        
-        let zombie_solution_table = &ZOMBIE_SOLUTION_TABLE.get_or_init(|| {
-            let mut solution_table: FxHashMap<ZombieType, Box<[SolutionEntry]>> = [
-                (ZombieType::ElitePaperZombie, vec![
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::Caltrop,
-                            Unlockable::WallNut,
-                        ].into_iter().collect(),
-                        uses: Some(6), //total guess
-                        ..Default::default()
-                    },
-                ].into_boxed_slice()),
-                (ZombieType::DancePolZombie, vec![
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::TallNut,
-                            Unlockable::WallNut,
-                        ].into_iter().collect(),
-                        uses: None,
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::EndoFlame,
-                            Unlockable::WallNut,
-                        ].into_iter().collect(),
-                        uses: None,
-                        ..Default::default()
-                    },
-                    !SolutionEntry {
-                        plants: [
-                            Unlockable::Chomper,
-                        ].into_iter().collect(),
-                        ..Default::default()
-                    },
-                ].into_boxed_slice()),
-                (ZombieType::PogoZombie, vec![
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::Umbrellaleaf,
-                            Unlockable::Cabbagepult,
-                        ].into_iter().collect(),
-                        uses: None,
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::Umbrellaleaf,
-                            Unlockable::Cornpult,
-                        ].into_iter().collect(),
-                        uses: None,
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::Umbrellaleaf,
-                            Unlockable::Garlic,
-                        ].into_iter().collect(),
-                        uses: None,
-                        ..Default::default()
-                    },
-                ].into_boxed_slice()),
-                (ZombieType::SuperPogoZombie, vec![
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::Umbrellaleaf,
-                            Unlockable::Cabbagepult,
-                        ].into_iter().collect(),
-                        uses: None,
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::Umbrellaleaf,
-                            Unlockable::Cornpult,
-                        ].into_iter().collect(),
-                        uses: None,
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::Umbrellaleaf,
-                            Unlockable::Garlic,
-                        ].into_iter().collect(),
-                        uses: None,
-                        ..Default::default()
-                    },
-                ].into_boxed_slice()),
-                (ZombieType::JackboxJumpZombie, vec![
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::Umbrellaleaf,
-                            Unlockable::Cabbagepult,
-                        ].into_iter().collect(),
-                        uses: None,
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::Umbrellaleaf,
-                            Unlockable::Cornpult,
-                        ].into_iter().collect(),
-                        uses: None,
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::Umbrellaleaf,
-                            Unlockable::Garlic,
-                        ].into_iter().collect(),
-                        uses: None,
-                        ..Default::default()
-                    },
-                ].into_boxed_slice()),
-                (ZombieType::SnowZombie, vec![
-                    !SolutionEntry {
-                        plants: [
-                            Unlockable::Chomper,
-                        ].into_iter().collect(),
-                        ..Default::default()
-                    },
-                    !SolutionEntry {
-                        plants: [
-                            Unlockable::Squash,
-                        ].into_iter().collect(),
-                        ..Default::default()
-                    },
-                ].into_boxed_slice()),
-                (ZombieType::MachineNutZombie, vec![
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::Umbrellaleaf,
-                        ].into_iter().collect(),
-                        uses: None,
-                        ..Default::default()
-                    },
-                ].into_boxed_slice()),
-                (ZombieType::SuperMachineNutZombie, vec![
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::Umbrellaleaf,
-                        ].into_iter().collect(),
-                        uses: None,
-                        ..Default::default()
-                    },
-                ].into_boxed_slice()),
-                (ZombieType::BalloonZombie, vec![
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::Blover,
-                        ].into_iter().collect(),
-                        ..Default::default()
-                    },
-                ].into_boxed_slice()),
-                (ZombieType::SuperCherryShooterZombie, vec![
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::CherryBomb,
-                            Unlockable::Pumpkin,
-                        ].into_iter().collect(),
-                        uses: None,
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::CherryBomb,
-                            Unlockable::WallNut,
-                        ].into_iter().collect(),
-                        uses: None,
-                        ..Default::default()
-                    },
-                ].into_boxed_slice()),
-                (ZombieType::CherryPaperZombie, vec![
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::CherryBomb,
-                            Unlockable::Pumpkin,
-                        ].into_iter().collect(),
-                        uses: None,
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::CherryBomb,
-                            Unlockable::WallNut,
-                        ].into_iter().collect(),
-                        uses: None,
-                        ..Default::default()
-                    },
-                ].into_boxed_slice()),
-                (ZombieType::CherryPaperZ95, vec![
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::CherryBomb,
-                            Unlockable::Pumpkin,
-                        ].into_iter().collect(),
-                        uses: None,
-                        ..Default::default()
-                    },
-                    SolutionEntry {
-                        plants: [
-                            Unlockable::CherryBomb,
-                            Unlockable::WallNut,
-                        ].into_iter().collect(),
-                        uses: None,
-                        ..Default::default()
-                    },
-                ].into_boxed_slice()),
-            ].into_iter().collect();
-            
-            for val in solution_table.values_mut() {
-                for solution in val {
-                    solution.plants.sort_unstable_by_key(|x| *x as isize);
-                }
-            }
-            
-            solution_table
-        });
+        let rules = RULES.get().unwrap();
+        let solution_table = &rules.flag_solutions;
+        let zombie_solution_table = &rules.zombie_solutions;
         
         let zombie_data = ZOMBIE_DATA.get().unwrap();
         let zombie = &zombie_data[zombie_idx as usize];
-        let flags = zombie.flags;
-        let problem_flags = flags.intersection(
-            ZombieFlags::HIGH_HEALTH |
-            ZombieFlags::V_HIGH_HEALTH |
-            ZombieFlags::FLIES |
-            ZombieFlags::GARG_TYPE |
-            ZombieFlags::EVIL_DEATH
-        );
+        let flags = self.zombie_flags(level_idx as usize, zombie_idx as usize);
+        let problem_flags = flags.intersection(PROBLEM_FLAGS);
         
         let attrib_flags = flags & !problem_flags;
         
@@ -1694,7 +1338,7 @@ impl RandomisationData {
                 solutions.remove(&entry);
             }
             
-            let freq = match flag {
+            let freq = match flag { //zombies a wave the plants have to keep up with
                 ZombieFlags::EVIL_DEATH => freq_data.max_frequency.get(&zombie_idx).unwrap().0
                     .max(*freq_data.first_flag_totals.get(&zombie_idx).unwrap()),
                 _ => *freq_data.first_2_flag_max.get(&zombie_idx).unwrap(),
@@ -1708,14 +1352,52 @@ impl RandomisationData {
         out_vec
     }
     
-    fn is_level_possible(&mut self, level_idx: u32, level_true_idx: u32, seed: u64) -> Result<(),Vec<ImpossibleReason>> {
-        static FIREPOWER_SOLUTIONS: OnceLock<FxHashMap<u8, Box<[SolutionEntry]>>> = OnceLock::new();
+    fn is_level_possible(&mut self, level_idx: u32, level_true_idx: u32, seed: u64) -> Result<Vec<ImpossibleReason>, GenerationError> { //an empty vec means the level is possible
+        let mut new_entries = HashMap::default();
+        let ret = self.impossible_reasons(level_idx, level_true_idx, seed, &mut new_entries);
+        self.cache_frequency_data(new_entries);
+        ret
+    }
+    
+    fn are_levels_possible(&mut self, levels: &[u8], level_true_idx: u32, seed: u64) -> Vec<Result<Vec<ImpossibleReason>, GenerationError>> { //in the same order as levels
+        let threads = self.restrictions_data.as_ref().unwrap().threads.min(levels.len());
+        if threads <= 1 {
+            return levels.iter().map(|level_idx| self.is_level_possible(*level_idx as u32, level_true_idx, seed)).collect();
+        }
+        
+        let this = &*self;
+        let chunks: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = levels.chunks(levels.len().div_ceil(threads)).map(|chunk| {
+                scope.spawn(move || {
+                    let mut new_entries = HashMap::default();
+                    let reasons: Vec<_> = chunk.iter().map(|level_idx| this.impossible_reasons(*level_idx as u32, level_true_idx, seed, &mut new_entries)).collect();
+                    (reasons, new_entries)
+                })
+            }).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
         
+        let mut ret = Vec::with_capacity(levels.len());
+        for (reasons, new_entries) in chunks {
+            ret.extend(reasons);
+            self.cache_frequency_data(new_entries);
+        }
+        ret
+    }
+    
+    fn impossible_reasons( //each level gets its own rng, so levels can be checked in any order
+        &self,
+        level_idx: u32,
+        level_true_idx: u32,
+        seed: u64,
+        new_entries: &mut FxHashMap<FrequencyCacheKey, FrequencyData>,
+    ) -> Result<Vec<ImpossibleReason>, GenerationError> {
         let mut rng = ChaCha8Rng::seed_from_u64(seed.wrapping_add(hash_str("more_plant_stuff")) ^ level_idx as u64);
         let mut ret = Vec::new();
         let zombie_data = ZOMBIE_DATA.get().unwrap();
         let level = &LEVEL_DATA.get().unwrap()[level_idx as usize - 1];
-        let solutions = Self::get_solutions_all();
+        let scene = self.scene(level_idx as usize);
+        let solutions = &RULES.get().unwrap().all_solutions;
         
         let mut used_solutions: FxHashMap<Solutions, u32> = HashMap::with_capacity_and_hasher(64, BuildHasherDefault::default());
         
@@ -1733,280 +1415,18 @@ impl RandomisationData {
             //].into_boxed_slice();
             //self.is_any_solution_satisfied(&basic_dps, level, &mut used_solutions, 3);
             
-            let firepower_solutions = FIREPOWER_SOLUTIONS.get_or_init(|| {
-                let mut firepower_solutions: FxHashMap<u8, Box<[SolutionEntry]>> = [
-                    (1, vec![
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::Peashooter,
-                            ].into_iter().collect(),
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::SmallPuff,
-                            ].into_iter().collect(),
-                            night: true,
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::FumeShroom,
-                            ].into_iter().collect(),
-                            night: true,
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::ScaredyShroom,
-                            ].into_iter().collect(),
-                            night: true,
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::ThreePeater,
-                            ].into_iter().collect(),
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::StarFruit,
-                            ].into_iter().collect(),
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::Cabbagepult,
-                                Unlockable::Cornpult,
-                            ].into_iter().collect(),
-                            uses: None,
-                            weight: 0.5,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::Cabbagepult,
-                                Unlockable::Garlic,
-                            ].into_iter().collect(),
-                            uses: None,
-                            weight: 0.5,
-                            ..Default::default()
-                        },
-                    ].into_boxed_slice()),
-                    (2, vec![
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::Peashooter,
-                            ].into_iter().collect(),
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::Peashooter,
-                                Unlockable::SmallPuff,
-                            ].into_iter().collect(),
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::SmallPuff,
-                                Unlockable::StarFruit,
-                            ].into_iter().collect(),
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::FumeShroom,
-                                Unlockable::ScaredyShroom,
-                            ].into_iter().collect(),
-                            night: true,
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::ThreePeater,
-                                Unlockable::Squash
-                            ].into_iter().collect(),
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::ThreePeater,
-                                Unlockable::TorchWood,
-                            ].into_iter().collect(),
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::Cactus,
-                                Unlockable::Plantern,
-                            ].into_iter().collect(),
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::Melonpult,
-                            ].into_iter().collect(),
-                            uses: None,
-                            ..Default::default()
-                        },
-                    ].into_boxed_slice()),
-                    (3, vec![
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::Peashooter,
-                                Unlockable::CherryBomb,
-                            ].into_iter().collect(),
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::Peashooter,
-                                Unlockable::TorchWood,
-                            ].into_iter().collect(),
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::Peashooter,
-                                Unlockable::SmallPuff,
-                            ].into_iter().collect(),
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::ThreePeater,
-                                Unlockable::Squash
-                            ].into_iter().collect(),
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::ThreePeater,
-                                Unlockable::TorchWood,
-                            ].into_iter().collect(),
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::StarFruit,
-                                Unlockable::Plantern,
-                            ].into_iter().collect(),
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::Cornpult,
-                                Unlockable::EndoFlame,
-                            ].into_iter().collect(),
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::Melonpult,
-                            ].into_iter().collect(),
-                            uses: None,
-                            ..Default::default()
-                        },
-                    ].into_boxed_slice()),
-                    (4, vec![
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::Peashooter,
-                                Unlockable::TorchWood,
-                            ].into_iter().collect(),
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::ThreePeater,
-                                Unlockable::TorchWood,
-                            ].into_iter().collect(),
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::StarFruit,
-                                Unlockable::Plantern,
-                                Unlockable::Pumpkin,
-                            ].into_iter().collect(),
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::Cornpult,
-                                Unlockable::EndoFlame,
-                            ].into_iter().collect(),
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::Melonpult,
-                                Unlockable::Jalapeno,
-                            ].into_iter().collect(),
-                            uses: None,
-                            ..Default::default()
-                        },
-                        SolutionEntry {
-                            plants: [
-                                Unlockable::Melonpult,
-                                Unlockable::Cabbagepult,
-                                Unlockable::Umbrellaleaf,
-                            ].into_iter().collect(),
-                            uses: None,
-                            ..Default::default()
-                        },
-                    ].into_boxed_slice()),
-                ].into_iter().collect();
-                
-                for val in firepower_solutions.values_mut() {
-                    for solution in val {
-                        solution.plants.sort_unstable_by_key(|x| *x as isize);
-                    }
-                }
-                
-                firepower_solutions
-            });
+            let firepower_solutions = &RULES.get().unwrap().firepower_solutions;
             
-            let solutions = firepower_solutions.get(&level.flags.unwrap_or(4)).unwrap();
+            let flags = self.flag_count(level_idx as usize).unwrap_or(4);
+            let solutions = firepower_solutions.get(&flags).ok_or(GenerationError::UnsupportedFlags(level_idx as usize, flags))?;
             let mut options: SmallVec<[SolutionEntry; 8]> = SmallVec::new();
             
-            let unlocked_plants = if let Some(conveyor_plants) = &level.conveyor_plants {
-                conveyor_plants
-            } else {
-                &self.restrictions_data.as_ref().unwrap().unlocked_plants
-            };
+            let unlocked_plants = self.level_plants(level_idx);
             
             for solution in solutions {
-                if !solution.night || level.level_type == LevelType::Night || level.level_type == LevelType::Fog {
-                    if solution.plants.iter().all(|plant| unlocked_plants.contains(plant)) {
-                        options.push(solution.clone());
-                    }
+                if (!solution.night || scene.is_night())
+                    && solution.plants.iter().all(|plant| unlocked_plants.contains(plant)) {
+                    options.push(solution.clone());
                 }
             }
             
@@ -2019,7 +1439,9 @@ impl RandomisationData {
                     cumulative_weights.push(total_weight);
                     total_weight += solution.weight as f64;
                 }
-                assert_ne!(total_weight, 0f64);
+                if total_weight == 0f64 {
+                    return Err(GenerationError::EmptyChoice("a firepower solution"));
+                }
                 let val = rng.next_u32() as f64 / 4_294_967_296. * total_weight;
                 let idx = cumulative_weights.partition_point(|csum| *csum <= val);
                 let solution = &options[idx - 1];
@@ -2027,31 +1449,31 @@ impl RandomisationData {
             }
         }
         
-        if level.conveyor_plants.is_none() {
-            let flags = level.flags.unwrap();
-            match level.level_type {
+        if level.conveyor_plants.is_none() || self.restrictions_data.as_ref().unwrap().conveyor_pools.contains_key(&(level_idx as u8)) {
+            let flags = self.flag_count(level_idx as usize)?;
+            match scene {
                 LevelType::Pool |
                 LevelType::Fog => {
                     match flags {
-                        1 => if !self.is_any_solution_satisfied(solutions.get(&Problem::Water1).unwrap(), level, &mut used_solutions, 3)
+                        1 => if !self.is_any_solution_satisfied(solutions.get(&Problem::Water1).unwrap(), level_idx, &mut used_solutions, 3)
                             && !(2..=4).contains(&(level_true_idx % 7)) {
                             ret.push(ImpossibleReason::NoWaterSolution);
                         }
-                        2 => if !self.is_any_solution_satisfied(solutions.get(&Problem::Water2).unwrap(), level, &mut used_solutions, 3)
+                        2 => if !self.is_any_solution_satisfied(solutions.get(&Problem::Water2).unwrap(), level_idx, &mut used_solutions, 3)
                             && !(2..=4).contains(&(level_true_idx % 7)) {
                             ret.push(ImpossibleReason::NoWaterSolution);
                         }
                         3 |
-                        4 => if !self.is_any_solution_satisfied(solutions.get(&Problem::Water34).unwrap(), level, &mut used_solutions, 3) {
+                        4 => if !self.is_any_solution_satisfied(solutions.get(&Problem::Water34).unwrap(), level_idx, &mut used_solutions, 3) {
                             ret.push(ImpossibleReason::NoWaterSolution);
                         }
-                        _ => unreachable!(),
+                        _ => return Err(GenerationError::UnsupportedFlags(level_idx as usize, flags)),
                     }
                 }
                 LevelType::Roof => {
                     match flags {
-                        1 => {}
-                        _ => if !self.is_any_solution_satisfied(solutions.get(&Problem::Roof).unwrap(), level, &mut used_solutions, 3) {
+                        1 if level.level_type == LevelType::Roof && level.flags == Some(1) => {} //the one flag roof levels come with pots already placed
+                        _ => if !self.is_any_solution_satisfied(solutions.get(&Problem::Roof).unwrap(), level_idx, &mut used_solutions, 3) {
                             ret.push(ImpossibleReason::NoPot);
                         }
                     }
@@ -2061,37 +1483,62 @@ impl RandomisationData {
         }
         
         
-        let spawns = self.restrictions_data.as_ref().unwrap().level_spawns.get(&(level_idx as u8)).unwrap().clone();
+        let mut spawns = self.restrictions_data.as_ref().unwrap().level_spawns.get(&(level_idx as u8)).ok_or(GenerationError::MissingLevelData(level_idx as usize))?.clone();
+        let progressive_weights = self.restrictions_data.as_ref().unwrap().progressive_weights && level_true_idx != 0;
+        if progressive_weights {
+            Self::scale_spawns(&mut spawns, level_true_idx as usize);
+        }
         let spawns_map: FxHashMap<u32, u32> = spawns.iter().map(|(k, v)| (*k, *v)).collect();
         let zombie_map = Self::get_zombie_map();
         let mut threshold_table = vec![999f32; zombie_data.len()];
-        if let Some(spawn_data) = self.compute_zombie_freq_data_cached(&spawns, level_idx as usize) {
+        let mut cost_caps: Vec<(Unlockable,u8)> = Vec::new();
+        let spawn_data = match self.frequency_data(&spawns, level_idx as usize, None, new_entries) {
+            Ok(spawn_data) => Some(spawn_data),
+            Err(GenerationError::NoFlags(_)) => None,
+            Err(err) => return Err(err),
+        };
+        if let Some(spawn_data) = spawn_data {
             let restrictions_data = self.restrictions_data.as_ref().unwrap();
-            let plant_data = restrictions_data.level_plants.get(&(level_idx as u8)).unwrap();
+            let plant_data = restrictions_data.level_plants.get(&(level_idx as u8)).ok_or(GenerationError::MissingLevelData(level_idx as usize))?;
             
-            let unlocked_plants = if let Some(conveyor_plants) = &level.conveyor_plants {
-                conveyor_plants
-            } else {
-                &self.restrictions_data.as_ref().unwrap().unlocked_plants
-            };
+            let unlocked_plants = self.level_plants(level_idx);
             
             let mut problem_vec: Vec<ProblemData> = spawns
                 .iter()
-                .flat_map(|(i, _)| self.get_zombie_solutions(&spawn_data, *i).into_iter())
+                .flat_map(|(i, _)| self.get_zombie_solutions(&spawn_data, level_idx, *i).into_iter())
                 .map(|mut data| {
-                    data.solutions = data.solutions
-                        .into_iter()
-                        .filter(|solution| {
-                            solution.plants
-                                .iter()
-                                .all(|plant| unlocked_plants.contains(&plant))
-                        })
-                        .collect();
+                    data.solutions.retain(|solution| {
+                        solution.plants
+                            .iter()
+                            .all(|plant| unlocked_plants.contains(plant))
+                    });
                     data
                 })
                 .collect();
             
-            let mut solution_set: FxHashMap<SolutionEntry, (f64, u32)> = HashMap::with_capacity_and_hasher(512, BuildHasherDefault::default());
+            if level.conveyor_plants.is_none() && !problem_vec.is_empty() { //conveyor plants are free
+                let banked_sun = self.banked_sun(level_idx, plant_data)?;
+                for problem in &problem_vec {
+                    let Some(cheapest) = problem.solutions.iter().min_by(|a, b| a.sun_cost(plant_data).total_cmp(&b.sun_cost(plant_data))) else {
+                        continue;
+                    };
+                    let first_wave = *spawn_data.first_wave_occurence_avgs.get(&problem.zombie_idx).unwrap() as usize;
+                    let budget = banked_sun[first_wave.clamp(1, banked_sun.len() - 1)];
+                    let cost = cheapest.sun_cost(plant_data);
+                    if cost > budget {
+                        for plant in &cheapest.plants {
+                            let max_cost = mul_to_u8(mul_from_u8(plant_data.menu[*plant as usize].1) * budget / cost);
+                            if let Some((_, cap)) = cost_caps.iter_mut().find(|(p, _)| p == plant) {
+                                *cap = (*cap).min(max_cost);
+                            } else {
+                                cost_caps.push((*plant, max_cost));
+                            }
+                        }
+                    }
+                }
+            }
+            
+            let mut solution_set: FxHashMap<SolutionEntry, (f64, u32)> = HashMap::with_capacity_and_hasher(512, BuildHasherDefault::default());
             let mut usage_array = [0f32; 41];
             let mut ldlist: Vec<(u32, Option<u32>)> = Vec::with_capacity(768);
             
@@ -2114,7 +1561,7 @@ impl RandomisationData {
                 let mut total_weight = 0f64;
                 let mut to_remove: FxHashSet<SolutionEntry> = HashSet::with_capacity_and_hasher(4, BuildHasherDefault::default());
                 for (entry, (cumulative_weight, _)) in solution_set.iter_mut() {
-                    let weight = cumulative_weight.min(4. / entry.compute_solution_cooldown(plant_data, &usage_array) as f64) * entry.weight as f64;
+                    let weight = cumulative_weight.min((WAVE_SECONDS / entry.compute_solution_cooldown(plant_data, &usage_array)) as f64) * entry.weight as f64;
                     *cumulative_weight = total_weight;
                     total_weight += weight;
                     if weight == 0. {
@@ -2127,7 +1574,9 @@ impl RandomisationData {
                     break;
                 }
                 
-                assert_ne!(total_weight, 0f64);
+                if total_weight == 0f64 {
+                    return Err(GenerationError::EmptyChoice("a zombie solution"));
+                }
                 let val = rng.next_u32() as f64 / 4_294_967_296. * total_weight;
                 let idx = solution_vec.partition_point(|(_, (csum, _))| *csum <= val);
                 let (solution, (_, idx)) = &solution_vec[idx - 1];
@@ -2145,7 +1594,7 @@ impl RandomisationData {
                     let problem_idx_idx = ((rng.next_u32() as u64 * problem_list.len() as u64) >> 32) as usize;
                     let problem_idx = problem_list[problem_idx_idx];
                     let problem = &mut problem_vec[problem_idx as usize];
-                    problem.freq = 3. / solution.compute_new_plant_utilization(plant_data, &mut usage_array, 3. / problem.freq);
+                    problem.freq = solution.answer_threat(plant_data, &mut usage_array, problem.freq);
                     if problem.freq == 0. {
                         problem_list.remove(problem_idx_idx);
                     } else {
@@ -2166,7 +1615,7 @@ impl RandomisationData {
             
             {
                 let (zombie_type, low_threshold, high_threshold) = (ZombieType::SnorkleZombie,0.1,0.45);
-                let zombie_idx = *zombie_map.get(&zombie_type).unwrap_or_else(|| panic!("Zombie type does not exist: \"{zombie_type:?}\""));
+                let zombie_idx = *zombie_map.get(&zombie_type).ok_or(GenerationError::MissingZombie(zombie_type))?;
                 if let Some((max_frequency, _)) = spawn_data.max_frequency.get(&zombie_idx) {
                     let max_frequency = *max_frequency;
                     if max_frequency >= low_threshold {
@@ -2198,8 +1647,8 @@ impl RandomisationData {
                             low_solutions.append(&mut high_solutions);
                         }
                         
-                        if !self.is_any_solution_satisfied(&high_solutions.into_boxed_slice(), level, &mut used_solutions, 1) {
-                            let threshold = if self.is_any_solution_satisfied(&low_solutions.into_boxed_slice(), level, &mut used_solutions, 1) {
+                        if !self.is_any_solution_satisfied(&high_solutions.into_boxed_slice(), level_idx, &mut used_solutions, 1) {
+                            let threshold = if self.is_any_solution_satisfied(&low_solutions.into_boxed_slice(), level_idx, &mut used_solutions, 1) {
                                 high_threshold
                             } else {
                                 low_threshold
@@ -2216,17 +1665,17 @@ impl RandomisationData {
                 (ZombieType::SubmarineZombie,2.0), //I don't like these guys
                 (ZombieType::BungiZombie,2.0), //its really funny when you get a bungee spam level, but it can be very problematic too
             ] {
-                let zombie_idx = *zombie_map.get(&zombie_type).unwrap_or_else(|| panic!("Zombie type does not exist: \"{zombie_type:?}\""));
+                let zombie_idx = *zombie_map.get(&zombie_type).ok_or(GenerationError::MissingZombie(zombie_type))?;
                 threshold_table[zombie_idx as usize] = threshold_table[zombie_idx as usize].min(true_max);
             }
             
-            if level.level_type == LevelType::Roof && level.flags.unwrap_or(1) == 1 {
+            if scene == LevelType::Roof && self.flag_count(level_idx as usize).unwrap_or(1) == 1 {
                 for (zombie_type, true_max) in [ //I will add zombies here as needed
                     (ZombieType::PogoZombie,2.0),
                     (ZombieType::SuperPogoZombie,0.8),
                     (ZombieType::JackboxJumpZombie,0.8),
                 ] {
-                    let zombie_idx = *zombie_map.get(&zombie_type).unwrap_or_else(|| panic!("Zombie type does not exist: \"{zombie_type:?}\""));
+                    let zombie_idx = *zombie_map.get(&zombie_type).ok_or(GenerationError::MissingZombie(zombie_type))?;
                     threshold_table[zombie_idx as usize] = threshold_table[zombie_idx as usize].min(true_max);
                 }
             }
@@ -2248,7 +1697,7 @@ impl RandomisationData {
                 }
             }
             
-            let new_frequencies = self.compute_zombie_freq_data_cached(&new_spawns, level_idx as usize).unwrap();
+            let new_frequencies = self.frequency_data(&new_spawns, level_idx as usize, Some(&spawn_data), new_entries)?; //only the weights changed, so most of the table carries over
             let mut bad_zombie_weight = 1.0;
             let mut bad_zombies: FxHashMap<u32,u32> = HashMap::with_capacity_and_hasher(16, BuildHasherDefault::default());
             
@@ -2264,8 +1713,13 @@ impl RandomisationData {
                 if new_val <= one_eigth_curved {
                     removed_zombies.insert(i);
                 } else if new_weight < old_weight {
-                    let old_val = Self::weight_curve_inverse((old_weight as f64 / zombie.default_weight as f64).log10());
-                    let new_val = Self::weight_curve_inverse(new_val.log10());
+                    let slot_mul = if progressive_weights { //the curve only covers the random part of the weight
+                        Self::zombie_weight_mul(level_true_idx as usize, i)
+                    } else {
+                        1.
+                    };
+                    let old_val = Self::weight_curve_inverse((old_weight as f64 / slot_mul / zombie.default_weight as f64).log10());
+                    let new_val = Self::weight_curve_inverse((new_val / slot_mul).log10());
                     bad_zombie_weight /= 1f64 + new_val - old_val;
                     bad_zombies.insert(i, new_weight);
                 }
@@ -2314,8 +1768,9 @@ impl RandomisationData {
                 solutions_weight_vec.push((solution, total_weight));
                 total_weight += weight;
             }
-            assert_ne!(solutions_weight_vec.len(), 0);
-            assert_ne!(total_weight, 0f64);
+            if solutions_weight_vec.is_empty() || total_weight == 0f64 {
+                return Err(GenerationError::EmptyChoice("a solution to keep usable"));
+            }
             let val = rng.next_u32() as f64 / 4_294_967_296. * total_weight;
             let idx = solutions_weight_vec.partition_point(|(_, csum)| *csum <= val);
             let solution = &solutions_weight_vec[idx - 1].0;
@@ -2324,13 +1779,13 @@ impl RandomisationData {
             }
         }
         let restrictions_data = self.restrictions_data.as_ref().unwrap();
-        let plant_data = restrictions_data.level_plants.get(&(level_idx as u8)).unwrap();
+        let plant_data = restrictions_data.level_plants.get(&(level_idx as u8)).ok_or(GenerationError::MissingLevelData(level_idx as usize))?;
         let mut weight_div = 1f64;
         let mut bad_plants_vec: Vec<(Unlockable,u8,u8,u8)> = Vec::with_capacity(16);
         for (unlockable_id, importance) in unlockable_importance.into_iter().enumerate() {
             if importance > 0 {
                 let unlockable: Unlockable = unsafe { transmute(unlockable_id as i8) };
-                let plant_true_idx = *restrictions_data.plant_map.get(&format!("{unlockable:?}")).unwrap();
+                let plant_true_idx = *restrictions_data.plant_map.get(&format!("{unlockable:?}")).ok_or(GenerationError::MissingPlant(unlockable))?;
                 let (min_useful_fr, max_useful_cd, max_useful_cs) = unlockable_useful_min_map.get(&unlockable).unwrap_or(&(0x50,0xE0,0xE0));
                 let maximum_firerate = if *min_useful_fr != 0x00 {
                     ((255f32 - *min_useful_fr as f32) / (0.8 + 0.2 * importance as f32)).round() as u8
@@ -2368,14 +1823,36 @@ impl RandomisationData {
             ret.push(ImpossibleReason::BadPlants(1./weight_div.sqrt(), bad_plants_vec));
         }
         
-        if ret.is_empty() {
-            Ok(())
-        } else {
-            Err(ret)
+        cost_caps.retain(|(plant, cap)| plant_data.menu[*plant as usize].1 > *cap);
+        if !cost_caps.is_empty() {
+            let weight_div = 1. + cost_caps.iter().map(|(plant, cap)| (plant_data.menu[*plant as usize].1 - cap) as f64 / 255.).sum::<f64>();
+            ret.push(ImpossibleReason::Unaffordable(1./weight_div.sqrt(), cost_caps)); //comes after BadPlants so it can build on its costs
         }
+        
+        Ok(ret)
     }
     
-    fn upgrade_to_plant(upgrade: Unlockable) -> Unlockable {
+    fn banked_sun(&self, level_idx: u32, plant_data: &LevelPlants) -> Result<Vec<f32>, GenerationError> { //the sun a player can have saved by the end of each wave of a level
+        let restrictions_data = self.restrictions_data.as_ref().unwrap();
+        let unlocked_plants = self.level_plants(level_idx);
+        let sun_bytes = &restrictions_data.level_sun[level_idx as usize - 1];
+        let mut producer: Option<(f32, f32, f32)> = None;
+        for (plant, sun) in SUN_PRODUCERS {
+            if unlocked_plants.contains(&plant) {
+                let plant_true_idx = *restrictions_data.plant_map.get(&format!("{plant:?}")).ok_or(GenerationError::MissingPlant(plant))?;
+                let sun = sun * sun_bytes.get(plant_true_idx as usize).map_or(1., |byte| mul_from_u8(*byte));
+                let cost = SolutionEntry::plant_cost(plant, plant_data);
+                if producer.is_none_or(|(best_cost, _, best_sun)| sun * best_cost > best_sun * cost) { //pays for itself the fastest
+                    producer = Some((cost, SolutionEntry::plant_cooldown(plant, plant_data), sun));
+                }
+            }
+        }
+        let sky_sun = if self.scene(level_idx as usize).is_night() {0.} else {SKY_SUN};
+        let waves = self.flag_count(level_idx as usize)? as usize * 10;
+        Ok(banked_sun(sky_sun, producer, waves))
+    }
+    
+    pub fn upgrade_to_plant(upgrade: Unlockable) -> Unlockable {
         match upgrade {
             Unlockable::TallNut => Unlockable::WallNut,
             Unlockable::SpikeRock => Unlockable::Caltrop,
@@ -2386,6 +1863,255 @@ impl RandomisationData {
         }
     }
     
+    fn apply_reasons(&mut self, level_idx: u8, reasons: Vec<ImpossibleReason>, level_true_idx: u32) -> Result<Option<f64>, GenerationError> { //the level's weight multiplier, none if it's impossible
+        let mut level_weight = 1f64;
+        let mut possible = true;
+        let mut plants_modified = false;
+        for reason in reasons {
+            match reason {
+                ImpossibleReason::NoWaterSolution => {
+                    self.stats.no_water_solution += 1;
+                    possible = false;
+                }
+                ImpossibleReason::InsufficientWaterSolution => {
+                    self.stats.insufficient_water_solution += 1;
+                    possible = false;
+                }
+                ImpossibleReason::NoPot => {
+                    self.stats.no_pot += 1;
+                    possible = false;
+                }
+                ImpossibleReason::FourFlag => {
+                    self.stats.four_flag += 1;
+                    possible = false;
+                }
+                ImpossibleReason::HardZombies(weight_mul, zombie_modifications) => {
+                    let restrictions_data = self.restrictions_data.as_mut().unwrap();
+                    if !zombie_modifications.is_empty() && restrictions_data.authored_spawns.contains(&level_idx) { //planned zombies are never lowered
+                        possible = false;
+                        continue;
+                    }
+                    level_weight *= weight_mul;
+                    let mut zombies = restrictions_data.level_spawns.get(&level_idx).unwrap().clone();
+                    let mut remove_idxs: SmallVec<[usize; 16]> = SmallVec::new();
+                    for (i, (zombie, weight)) in zombies.iter_mut().enumerate() {
+                        if let Some(new_weight) = zombie_modifications.get(zombie) {
+                            *weight = *new_weight;
+                            if restrictions_data.progressive_weights && level_true_idx != 0 { //the new weight is scaled, but the stored spawns aren't
+                                *weight = Self::scale_weight(*new_weight, 1. / Self::zombie_weight_mul(level_true_idx as usize, *zombie));
+                            }
+                            if *new_weight == 0 {
+                                remove_idxs.push(i);
+                            }
+                        }
+                    }
+                    remove_idxs.sort_unstable();
+                    for i in remove_idxs.iter().rev() {
+                        zombies.remove(*i);
+                    }
+                    restrictions_data.modified_level_spawns.insert(level_idx, zombies);
+                    if !zombie_modifications.is_empty() {
+                        restrictions_data.level_modifications.entry(level_idx).or_default().hard_zombies = true;
+                    }
+                }
+                ImpossibleReason::BadPlants(weight_mul, new_plants) => {
+                    let restrictions_data = self.restrictions_data.as_mut().unwrap();
+                    let mut plants = (*restrictions_data.level_plants.get(&level_idx).unwrap()).clone();
+                    let mut authored_capped = false;
+                    for (unlockable, max_firerate, max_cost, max_cooldown) in new_plants {
+                        let plant_true_idx = *restrictions_data.plant_map.get(&format!("{unlockable:?}")).ok_or(GenerationError::MissingPlant(unlockable))?;
+                        let (cd, cs) = &mut plants.menu[unlockable as usize];
+                        let fr = &mut plants.all[plant_true_idx as usize];
+                        
+                        if let Some(planned) = restrictions_data.authored_plant(level_idx, unlockable) {
+                            authored_capped |= (planned.cooldown.is_some() && *cd > max_cooldown) || (planned.cost.is_some() && *cs > max_cost) || (planned.firerate.is_some() && *fr > max_firerate);
+                        }
+                        *cd = (*cd).min(max_cooldown);
+                        *cs = (*cs).min(max_cost);
+                        *fr = (*fr).min(max_firerate);
+                    }
+                    if authored_capped { //planned stats are never capped
+                        possible = false;
+                        continue;
+                    }
+                    level_weight *= weight_mul;
+                    restrictions_data.modified_level_plants.insert(level_idx, plants);
+                    restrictions_data.level_modifications.entry(level_idx).or_default().bad_plants = true;
+                    plants_modified = true;
+                }
+                ImpossibleReason::Unaffordable(weight_mul, max_costs) => {
+                    let restrictions_data = self.restrictions_data.as_mut().unwrap();
+                    let mut plants = if plants_modified {
+                        restrictions_data.modified_level_plants.get(&level_idx)
+                    } else {
+                        restrictions_data.level_plants.get(&level_idx)
+                    }.unwrap().clone();
+                    let mut authored_capped = false;
+                    for (unlockable, max_cost) in max_costs {
+                        let (_, cs) = &mut plants.menu[unlockable as usize];
+                        authored_capped |= *cs > max_cost && restrictions_data.authored_plant(level_idx, unlockable).is_some_and(|planned| planned.cost.is_some());
+                        *cs = (*cs).min(max_cost);
+                    }
+                    if authored_capped {
+                        possible = false;
+                        continue;
+                    }
+                    level_weight *= weight_mul;
+                    restrictions_data.modified_level_plants.insert(level_idx, plants);
+                    restrictions_data.level_modifications.entry(level_idx).or_default().unaffordable = true;
+                }
+            }
+        }
+        Ok(possible.then_some(level_weight))
+    }
+    
+    //gives a level new zombies and plant stats from a seed of its own, without changing which level or plant goes in any slot
+    #[allow(clippy::too_many_arguments)]
+    fn reroll_level(
+        &mut self,
+        level_idx: usize,
+        slot: usize,
+        rerolls: u8,
+        seed: u64,
+        blacklisted: bool,
+        enum_variants: &FxHashMap<String, u64>,
+        fuse_data: &FxHashMap<u32,[u32;2]>,
+        params: &DifficultyParams,
+    ) -> Result<u8, GenerationError> { //returns the reroll count used, counting on from rerolls until the level is possible
+        let (_, plant_ids, rev_map) = Self::get_plant_map_and_ids(enum_variants);
+        let non_fused_ids = Self::non_fused_ids(&plant_ids, fuse_data);
+        let scene = self.scene(level_idx);
+        for rerolls in (rerolls..=u8::MAX).take(32) {
+            let sub_seed = seed ^ hash_str(&format!("reroll {slot} {rerolls}"));
+            let mut weights_rng = ChaCha8Rng::seed_from_u64(sub_seed ^ hash_str("zombie_weights"));
+            let (spawns, _) = Self::randomise_level_spawns(sub_seed ^ hash_str(&level_idx.to_string()), level_idx, scene, blacklisted, &mut weights_rng, params);
+            let plants = self.randomise_level_plants(&mut PlantAttrRngs::new(sub_seed), &plant_ids, &rev_map, &non_fused_ids, fuse_data, params);
+            
+            let restrictions_data = self.restrictions_data.as_mut().unwrap();
+            if !restrictions_data.authored_spawns.contains(&(level_idx as u8)) {
+                restrictions_data.level_spawns.insert(level_idx as u8, spawns);
+            }
+            if !restrictions_data.authored_plants.contains_key(&(level_idx as u8)) { //a level with any planned plant stats keeps its plant table, so its unplanned plants only change by the caps its new zombies need
+                restrictions_data.level_plants.insert(level_idx as u8, plants);
+            }
+            //the same tables as for a whole seed, from the sub-seed, so the rerolled level's row is taken from them
+            let zombie_stats = Self::randomise_zombie_stats(sub_seed, !restrictions_data.zombie_stats[0].is_empty(), params);
+            let plant_damage = Self::randomise_plant_stat(sub_seed, "plant_damage", !restrictions_data.plant_damage[0].is_empty(), Unlockable::Peashooter, enum_variants, fuse_data, params);
+            let level_sun    = Self::randomise_plant_stat(sub_seed, "sun_production", !restrictions_data.level_sun[0].is_empty(), Unlockable::SunFlower, enum_variants, fuse_data, params);
+            restrictions_data.zombie_stats[level_idx - 1] = zombie_stats[level_idx - 1].clone();
+            restrictions_data.plant_damage[level_idx - 1] = plant_damage[level_idx - 1].clone();
+            restrictions_data.level_sun[level_idx - 1]    = level_sun[level_idx - 1].clone();
+            restrictions_data.modified_level_spawns.remove(&(level_idx as u8));
+            restrictions_data.modified_level_plants.remove(&(level_idx as u8));
+            restrictions_data.level_modifications.remove(&(level_idx as u8));
+            
+            let reasons = self.is_level_possible(level_idx as u32, slot as u32 - 1, sub_seed)?;
+            if self.apply_reasons(level_idx as u8, reasons, slot as u32 - 1)?.is_some() {
+                return Ok(rerolls);
+            }
+        }
+        Err(GenerationError::ImpossibleLevels(vec![level_idx]))
+    }
+    
+    fn check_plan(plan: &Plan, plant_options: &PlantOptions, excluded: &FxHashSet<Unlockable>) -> Result<(), GenerationError> {
+        let planned_first_plant = plan.slot(1).and_then(|planned| planned.unlock);
+        if let Some(plant) = planned_first_plant {
+            if let Some(other) = plant_options.first_plant.filter(|other| *other != plant) {
+                return Err(GenerationError::InvalidPlan(format!("Slot 1 unlocks {plant:?}, but the first plant is set to {other:?}")));
+            }
+            if Self::upgrade_to_plant(plant) != plant {
+                return Err(GenerationError::InvalidFirstPlant(plant));
+            }
+        }
+        let first_plant = planned_first_plant.or(plant_options.first_plant);
+        
+        for planned in &plan.slots {
+            let Some(plant) = planned.unlock else {
+                continue;
+            };
+            if excluded.contains(&plant) {
+                return Err(GenerationError::ExcludedPlant(plant));
+            }
+            if planned.slot != 1 && first_plant == Some(plant) {
+                return Err(GenerationError::InvalidPlan(format!("Slot {} unlocks {plant:?}, but it is the first plant", planned.slot)));
+            }
+            if plant_options.early_plants.contains(&plant) && planned.slot > plant_options.early_levels {
+                return Err(GenerationError::InvalidPlan(format!("Slot {} unlocks {plant:?}, but it is an early plant, so it needs one of the first {} slots", planned.slot, plant_options.early_levels)));
+            }
+            
+            let base = Self::upgrade_to_plant(plant);
+            let base_slot = if matches!(base, Unlockable::Peashooter | Unlockable::SunFlower) || first_plant == Some(base) {
+                Some(1)
+            } else {
+                plan.slots.iter().find(|other| other.unlock == Some(base)).map(|other| other.slot)
+            };
+            if base != plant && base_slot.is_none_or(|base_slot| base_slot >= planned.slot) { //otherwise the base plant could end up in this slot instead
+                return Err(GenerationError::InvalidPlan(format!("Slot {} unlocks {plant:?}, so {base:?} has to be unlocked by an earlier planned slot", planned.slot)));
+            }
+        }
+        Ok(())
+    }
+    
+    //puts the plan's zombies and plant stats into their levels before anything is checked
+    fn apply_planned_levels(
+        &mut self,
+        plan: &Plan,
+        blacklist_set: &mut FxHashSet<u32>,
+        enum_variants: &FxHashMap<String, u64>,
+        fuse_data: &FxHashMap<u32,[u32;2]>,
+    ) -> Result<(), GenerationError> {
+        let zombie_data = ZOMBIE_DATA.get().unwrap();
+        let zombie_map = Self::get_zombie_map();
+        let (_, plant_ids, _) = Self::get_plant_map_and_ids(enum_variants);
+        let restrictions_data = self.restrictions_data.as_mut().unwrap();
+        
+        for planned in &plan.levels {
+            let level_idx = planned.level;
+            if let Some(zombies) = &planned.zombies {
+                let scene = restrictions_data.scenes[level_idx as usize - 1];
+                let mut spawns: Vec<(u32, u32)> = Vec::with_capacity(zombies.len());
+                for (zombie, weight) in zombies {
+                    let zombie_idx = *zombie_map.get(zombie).ok_or(GenerationError::MissingZombie(*zombie))?;
+                    let data = &zombie_data[zombie_idx as usize];
+                    if data.flags.contains(ZombieFlags::IS_BANNED) {
+                        return Err(GenerationError::InvalidPlan(format!("{zombie:?} in level {level_idx} can't be spawned by the randomiser")));
+                    }
+                    if matches!(data.allowed_lanes, ZombieLanes::Water) && !scene.has_water() {
+                        return Err(GenerationError::InvalidPlan(format!("{zombie:?} in level {level_idx} needs water, but the level's scene has none")));
+                    }
+                    spawns.push((zombie_idx, *weight));
+                }
+                spawns.sort_unstable();
+                if !spawns.iter().any(|(zombie_idx, _)| zombie_data[*zombie_idx as usize].flags.contains(ZombieFlags::IS_ODYSSEY)) {
+                    blacklist_set.remove(&(level_idx as u32));
+                }
+                restrictions_data.level_spawns.insert(level_idx, spawns);
+                restrictions_data.authored_spawns.insert(level_idx);
+            }
+            
+            if !planned.plants.is_empty() {
+                let plants = restrictions_data.level_plants.get_mut(&level_idx).unwrap();
+                for planned_plant in &planned.plants {
+                    let plant = planned_plant.plant;
+                    if let Some(cooldown) = planned_plant.cooldown {
+                        plants.menu[plant as usize].0 = mul_to_u8(cooldown);
+                    }
+                    if let Some(cost) = planned_plant.cost {
+                        plants.menu[plant as usize].1 = mul_to_u8(cost);
+                    }
+                    if let Some(firerate) = planned_plant.firerate {
+                        let plant_true_idx = *restrictions_data.plant_map.get(&format!("{plant:?}")).ok_or(GenerationError::MissingPlant(plant))?;
+                        plants.all[plant_true_idx as usize] = mul_to_u8(firerate);
+                    }
+                }
+                Self::set_fusion_firerates(&mut plants.all, &plant_ids, fuse_data);
+                restrictions_data.authored_plants.insert(level_idx, planned.plants.clone());
+            }
+        }
+        Ok(())
+    }
+    
+    #[allow(clippy::too_many_arguments)]
     fn pick_level(
         &mut self,
         remaining_levels: &Vec<u8>,
@@ -2393,64 +2119,40 @@ impl RandomisationData {
         blacklist_set: &FxHashSet<u32>,
         cattail_girl: bool,
         rng: &mut ChaCha8Rng,
+        odyssey_levels: usize,
         seed: u64,
-    ) -> usize {
+    ) -> Result<usize, GenerationError> {
         let mut possible_levels: SmallVec<[(usize,f64); 64]> = SmallVec::new();
         let mut impossible_levels: SmallVec<[usize; 64]> = SmallVec::new();
         let mut total_weight = 0f64;
         
+        let restrictions_data = self.restrictions_data.as_ref().unwrap();
+        let only_early = cattail_girl && !restrictions_data.early_levels.is_empty()
+            && restrictions_data.early_levels.len() >= restrictions_data.early_deadline.saturating_sub(45 - remaining_levels.len());
+        let skipped = |level_idx: &u8| (only_early && !restrictions_data.early_levels.contains(level_idx)) || restrictions_data.planned_levels.contains_key(level_idx); //planned levels wait for their slot
+        let blacklisted = |level_idx: &u8| blacklist_set.contains(&(*level_idx as u32)) && remaining_levels.len() > odyssey_levels;
+        
+        let checked_levels: Vec<u8> = remaining_levels.iter().copied().filter(|level_idx| !skipped(level_idx) && !blacklisted(level_idx)).collect();
+        let level_true_idx = if cattail_girl {45 - remaining_levels.len() as u32} else {0};
+        let mut all_reasons = self.are_levels_possible(&checked_levels, level_true_idx, seed).into_iter();
+        
         for level_idx in remaining_levels {
             let mut level_weight = 1f64;
             
-            if !(blacklist_set.contains(&(*level_idx as u32)) && remaining_levels.len() > 15) &&
-                match self.is_level_possible(*level_idx as u32, if cattail_girl {45 - remaining_levels.len() as u32} else {0}, seed) {
-                Ok(()) => true,
-                Err(reasons) => {
-                    let mut possible = true;
-                    for reason in reasons {
-                        match reason {
-                            ImpossibleReason::NoWaterSolution |
-                            ImpossibleReason::InsufficientWaterSolution |
-                            ImpossibleReason::NoPot |
-                            ImpossibleReason::FourFlag => possible = false,
-                            ImpossibleReason::HardZombies(weight_mul, zombie_modifications) => {
-                                level_weight *= weight_mul;
-                                let restrictions_data = self.restrictions_data.as_mut().unwrap();
-                                let mut zombies = restrictions_data.level_spawns.get(level_idx).unwrap().clone();
-                                let mut remove_idxs: SmallVec<[usize; 16]> = SmallVec::new();
-                                for (i, (zombie, weight)) in zombies.iter_mut().enumerate() {
-                                    if let Some(new_weight) = zombie_modifications.get(zombie) {
-                                        *weight = *new_weight;
-                                        if *new_weight == 0 {
-                                            remove_idxs.push(i);
-                                        }
-                                    }
-                                }
-                                remove_idxs.sort_unstable();
-                                for i in remove_idxs.iter().rev() {
-                                    zombies.remove(*i);
-                                }
-                                restrictions_data.modified_level_spawns.insert(*level_idx, zombies);
-                            }
-                            ImpossibleReason::BadPlants(weight_mul, new_plants) => {
-                                level_weight *= weight_mul;
-                                let restrictions_data = self.restrictions_data.as_mut().unwrap();
-                                let mut plants = (*restrictions_data.level_plants.get(level_idx).unwrap()).clone();
-                                for (unlockable, max_firerate, max_cost, max_cooldown) in new_plants {
-                                    let plant_true_idx = *restrictions_data.plant_map.get(&format!("{unlockable:?}")).unwrap();
-                                    let (cd, cs) = &mut plants.menu[unlockable as usize];
-                                    let fr = &mut plants.all[plant_true_idx as usize];
-                                    
-                                    *cd = (*cd).min(max_cooldown);
-                                    *cs = (*cs).min(max_cost);
-                                    *fr = (*fr).min(max_firerate);
-                                }
-                                restrictions_data.modified_level_plants.insert(*level_idx, plants);
-                            }
-                        }
+            let restrictions_data = self.restrictions_data.as_ref().unwrap();
+            if (only_early && !restrictions_data.early_levels.contains(level_idx)) || restrictions_data.planned_levels.contains_key(level_idx) {
+                continue;
+            }
+            
+            if !(blacklist_set.contains(&(*level_idx as u32)) && remaining_levels.len() > odyssey_levels) && {
+                let reasons = all_reasons.next().unwrap()?;
+                match self.apply_reasons(*level_idx, reasons, level_true_idx)? {
+                    Some(weight_mul) => {
+                        level_weight *= weight_mul;
+                        true
                     }
-                    possible
-                },
+                    None => false,
+                }
             } {
                 if let Some((_, new_weight)) = predetermined_level_plants.get(level_idx) {
                     level_weight = *new_weight as f64;
@@ -2463,32 +2165,35 @@ impl RandomisationData {
         }
         
         if possible_levels.is_empty() {
-            panic!("These levels were impossible to satisfy: {impossible_levels:?}");
+            return Err(GenerationError::ImpossibleLevels(impossible_levels.into_vec()));
+        }
+        if total_weight == 0f64 {
+            return Err(GenerationError::EmptyChoice("a level"));
         }
-        assert_ne!(possible_levels.len(), 0);
-        assert_ne!(total_weight, 0f64);
         
         let val = rng.next_u32() as f64 / 4_294_967_296. * total_weight;
         let idx = possible_levels.partition_point(|(_, csum)| *csum <= val);
         //println!("{val}, {possible_levels:?}");
-        possible_levels[idx - 1].0
+        Ok(possible_levels[idx - 1].0)
     }
     
+    #[allow(clippy::too_many_arguments)]
     fn assign_solutions(
         &mut self,
-        possible_solutions: FxHashMap<Problem, Solutions>,
+        possible_solutions: &FxHashMap<Problem, Solutions>,
         predetermined_level_plants: &mut FxHashMap<u8, (Unlockable, f32)>,
         remaining_levels: &mut Vec<u8>,
         blacklist_set: &FxHashSet<u32>,
         rng: &mut ChaCha8Rng,
+        odyssey_levels: usize,
         seed: u64,
-    ) {
+    ) -> Result<(), GenerationError> {
         let mut idx_vec: Vec<(u32, u32)> = Vec::with_capacity(possible_solutions.len());
         for i in 0..possible_solutions.len() {
             idx_vec.push((i as u32, rng.next_u32()));
         }
         idx_vec.sort_by_key(|(_, key)| *key);
-        let problem_solution_vec: Vec<(Problem, Solutions)> = possible_solutions.into_iter().collect();
+        let problem_solution_vec: Vec<(Problem, Solutions)> = possible_solutions.iter().map(|(problem, solutions)| (*problem, solutions.clone())).collect();
         let problem_solution_vec: Vec<(Problem, Solutions)> = idx_vec.iter().map(|(idx, _)| problem_solution_vec[*idx as usize].clone()).collect();
         
         for (problem, possible_solutions) in problem_solution_vec.iter() {
@@ -2502,11 +2207,18 @@ impl RandomisationData {
                         weight += 4f64;
                     }
                 }
+                if solution.iter().any(|unlock| restrictions_data.excluded_plants.contains(unlock)) {
+                    weight = 0f64;
+                }
                 solution_weights.push(total_weight);
                 total_weight += weight;
             }
-            assert_ne!(solution_weights.len(), 0);
-            assert_ne!(total_weight, 0f64);
+            if solution_weights.is_empty() {
+                return Err(GenerationError::EmptyChoice("a plant solution"));
+            }
+            if total_weight == 0f64 {
+                return Err(GenerationError::NoAllowedSolution(*problem));
+            }
             
             let val = rng.next_u32() as f64 / 4_294_967_296. * total_weight;
             let idx = solution_weights.partition_point(|csum| *csum <= val);
@@ -2530,7 +2242,7 @@ impl RandomisationData {
                     Problem::NoPuff           => 2.5,
                 };
                 if !restrictions_data.unlocked_plants.contains(unlock) {
-                    let level_idx = Self::pick_level(self, remaining_levels, predetermined_level_plants, blacklist_set, false, rng, seed);
+                    let level_idx = Self::pick_level(self, remaining_levels, predetermined_level_plants, blacklist_set, false, rng, odyssey_levels, seed)?;
                     let level_idx_idx = remaining_levels.binary_search(&(level_idx as u8)).unwrap();
                     remaining_levels.remove(level_idx_idx);
                     let restrictions_data = self.restrictions_data.as_mut().unwrap();
@@ -2541,9 +2253,15 @@ impl RandomisationData {
                 }
             }
         }
+        
+        Ok(())
     }
     
-    pub fn restrictions(seed: u64, meta: &IL2CppDumper, fuse_data: &FxHashMap<u32,[u32;2]>) -> Self {
+    pub fn restrictions(seed: u64, options: &GenerationOptions, enum_variants: &FxHashMap<String, u64>, fuse_data: &FxHashMap<u32,[u32;2]>) -> Result<Self, GenerationError> {
+        let fusions = Self::randomise_fusions(seed, options.fusions, fuse_data);
+        let fuse_data = fusions.as_ref().unwrap_or(fuse_data);
+        let params = options.difficulty.params();
+        let plant_options = &options.plant_options;
         let zombie_data = ZOMBIE_DATA.get().unwrap();
         let level_data = LEVEL_DATA.get().unwrap();
         
@@ -2562,19 +2280,51 @@ impl RandomisationData {
             costs: Some(Vec::new()),
             spawns: Some(Vec::new()),
             freqs: Some(Vec::new()),
+            conveyors: options.conveyors.then(Vec::new),
+            flag_counts: options.flag_counts.then(Vec::new),
+            scenes: options.scenes.then(Vec::new),
+            zombie_stats: options.zombie_stats.then(Vec::new),
+            plant_damage: options.plant_damage.then(Vec::new),
+            sun_production: options.sun_production.then(Vec::new),
+            fusions: fusions.clone(),
+            weight_scaling: options.progressive_weights.then(Self::weight_scaling_table),
             sound_seeds: Some(sound_seeds),
+            rerolls: Vec::new(),
+            stats: GenerationStats::default(),
             restrictions_data: Some(RestrictionsData {
                 frequency_cache: HashMap::default(),
                 level_spawns: HashMap::default(),
                 modified_level_spawns: HashMap::default(),
                 level_plants: HashMap::default(),
                 modified_level_plants: HashMap::default(),
+                level_modifications: HashMap::default(),
                 plant_map: HashMap::default(),
                 unlocked_plants: HashSet::default(),
+                excluded_plants: plant_options.excluded_set(),
+                early_levels: Vec::new(),
+                early_deadline: plant_options.early_levels as usize,
+                conveyor_pools: HashMap::default(),
+                flag_counts: Self::randomise_flag_counts(seed, options.flag_counts)
+                    .into_iter()
+                    .zip(1..)
+                    .filter_map(|(flags, level)| Some((level, flags?)))
+                    .collect(),
+                scenes: Self::randomise_scenes(seed, options.scenes),
+                zombie_stats: Self::randomise_zombie_stats(seed, options.zombie_stats, &params),
+                plant_damage: Self::randomise_plant_stat(seed, "plant_damage", options.plant_damage, Unlockable::Peashooter, enum_variants, fuse_data, &params),
+                level_sun: Self::randomise_plant_stat(seed, "sun_production", options.sun_production, Unlockable::SunFlower, enum_variants, fuse_data, &params),
+                planned_levels: HashMap::default(),
+                authored_spawns: HashSet::default(),
+                authored_plants: HashMap::default(),
+                progressive_weights: options.progressive_weights,
+                threads: match options.threads {
+                    0 => available_parallelism().map_or(1, |threads| threads.get()),
+                    threads => threads,
+                },
             }),
         };
         
-        ret.randomise_plant_attrs(meta, fuse_data, seed);
+        ret.randomise_plant_attrs(enum_variants, fuse_data, seed, &params);
         
         let mut remaining_levels: Vec<u8> = (2..=45).collect();
         ret.level_order.push(1);
@@ -2596,53 +2346,89 @@ impl RandomisationData {
         if let Some(costs) = ret.costs.as_mut() {
             costs.push(Vec::new());
         }
+        if let Some(conveyors) = ret.conveyors.as_mut() {
+            conveyors.push(Vec::new());
+        }
+        let first_flags = ret.flag_count(1).unwrap_or(0);
+        if let Some(flag_counts) = ret.flag_counts.as_mut() {
+            flag_counts.push(vec![first_flags]);
+        }
+        let first_scene = ret.scene(1);
+        if let Some(scenes) = ret.scenes.as_mut() {
+            scenes.push(vec![first_scene as u8]);
+        }
+        let restrictions_data = ret.restrictions_data.as_ref().unwrap();
+        if let Some(zombie_stats) = ret.zombie_stats.as_mut() {
+            zombie_stats.push(restrictions_data.zombie_stats[0].clone());
+        }
+        if let Some(plant_damage) = ret.plant_damage.as_mut() {
+            plant_damage.push(restrictions_data.plant_damage[0].clone());
+        }
+        if let Some(sun_production) = ret.sun_production.as_mut() {
+            sun_production.push(restrictions_data.level_sun[0].clone());
+        }
         
         let restrictions_data = ret.restrictions_data.as_mut().unwrap();
+        plant_options.check(&restrictions_data.excluded_plants)?;
+        if let Some((slot, _)) = options.rerolls.iter().find(|(slot, _)| !(2..=45).contains(slot)) {
+            return Err(GenerationError::InvalidReroll(*slot));
+        }
+        if let Some(plan) = &options.plan {
+            Self::check_plan(plan, plant_options, &restrictions_data.excluded_plants)?;
+        }
         
         let mut blacklist_vec: Vec<(u32, u32)> = Vec::with_capacity(32);
-        let mut blacklist_set: FxHashSet<u32> = HashSet::with_capacity_and_hasher(15, BuildHasherDefault::default());
+        let mut blacklist_set: FxHashSet<u32> = HashSet::with_capacity_and_hasher(params.odyssey_levels, BuildHasherDefault::default());
         for (i, level) in level_data.iter().enumerate().skip(1) {
-            if let Some(flags) = level.flags {
-                if level.conveyor_plants.is_none() && flags > 1 {
+            if let Some(flags) = restrictions_data.flag_counts.get(&(i as u8 + 1)) {
+                if level.conveyor_plants.is_none() && *flags > 1 {
                     blacklist_vec.push((i as u32 + 1, level_rng.next_u32()))
                 }
             }
         }
         blacklist_vec.sort_by_key(|(_, key)| *key);
-        for (level, _) in blacklist_vec.iter().take(15) {
+        for (level, _) in blacklist_vec.iter().take(params.odyssey_levels) {
             blacklist_set.insert(*level);
         }
         for i in 2..=45 {
-            let mut still_blacklist = false;
-            let mut vec = Vec::new();
-            let mut bitfield = Self::randomise_spawns_no_restrictions(
+            let (vec, still_blacklist) = Self::randomise_level_spawns(
                 seed ^ hash_str(&i.to_string()),
                 i,
-                if blacklist_set.contains(&(i as u32)) {31} else {1},
+                restrictions_data.scenes[i - 1],
+                blacklist_set.contains(&(i as u32)),
+                &mut weights_rng,
+                &params,
             );
-            for (byte_idx, byte) in bitfield.iter_mut().enumerate() {
-                loop {
-                    let bit_pos = byte.trailing_zeros();
-                    if bit_pos == 8 {
-                        break;
-                    }
-                    *byte ^= 1 << bit_pos;
-                    let idx = bit_pos as usize + byte_idx * 8;
-                    let mut weight_mul = 10f64.powf(Self::weight_curve(weights_rng.next_u32()));
-                    if idx == 0 {
-                        weight_mul = weight_mul.max(1.0);
-                    }
-                    vec.push((idx as u32, (weight_mul * zombie_data[idx].default_weight as f64).round() as u32));
-                    if zombie_data[idx].flags.contains(ZombieFlags::IS_ODYSSEY) {
-                        still_blacklist = true;
-                    }
-                }
-            }
             restrictions_data.level_spawns.insert(i as u8, vec);
             if !still_blacklist {
                 blacklist_set.remove(&(i as u32));
             }
         }
+        if let Some(plan) = &options.plan {
+            ret.apply_planned_levels(plan, &mut blacklist_set, enum_variants, fuse_data)?;
+        }
+        
+        if options.conveyors {
+            let mut conveyor_rng = ChaCha8Rng::seed_from_u64(seed ^ hash_str("conveyors"));
+            for (i, level) in level_data.iter().enumerate().filter(|(_, level)| level.conveyor_plants.is_some()) {
+                for _ in 0..32 { //if no pool works the level keeps its normal plants
+                    let pool = Self::randomise_conveyor_pool(level, ret.flag_count(i + 1).ok(), &mut conveyor_rng);
+                    ret.restrictions_data.as_mut().unwrap().conveyor_pools.insert(i as u8 + 1, pool);
+                    let reasons = ret.is_level_possible(i as u32 + 1, 0, seed)?;
+                    let restrictions_data = ret.restrictions_data.as_ref().unwrap();
+                    if reasons.iter().all(|reason| match reason { //apply_reasons lowers the zombies or caps the plants for these once the level is placed with its pool, unless the plan set them
+                        ImpossibleReason::HardZombies(_, modifications) => modifications.is_empty() || !restrictions_data.authored_spawns.contains(&(i as u8 + 1)),
+                        ImpossibleReason::BadPlants(..) |
+                        ImpossibleReason::Unaffordable(..) => !restrictions_data.authored_plants.contains_key(&(i as u8 + 1)),
+                        _ => false,
+                    }) {
+                        break;
+                    }
+                    ret.restrictions_data.as_mut().unwrap().conveyor_pools.remove(&(i as u8 + 1));
+                }
+            }
+        }
+        let restrictions_data = ret.restrictions_data.as_mut().unwrap();
         
         let mut plant_order: Vec<Unlockable> = Vec::with_capacity(41);
         let first_plant_options = [
@@ -2660,7 +2446,14 @@ impl RandomisationData {
             Unlockable::Melonpult,
         ];
         
-        let first_plant = first_plant_options[((plants_rng.next_u32() as u64 * first_plant_options.len() as u64) >> 32) as usize];
+        let first_plant_options: SmallVec<[Unlockable; 16]> = first_plant_options.into_iter().filter(|plant| !restrictions_data.excluded_plants.contains(plant)).collect();
+        let pinned_first_plant = options.plan.as_ref().and_then(|plan| plan.slot(1)?.unlock).or(plant_options.first_plant);
+        if first_plant_options.is_empty() && pinned_first_plant.is_none() {
+            return Err(GenerationError::EmptyChoice("the first plant"));
+        }
+        let random_idx = ((plants_rng.next_u32() as u64 * first_plant_options.len() as u64) >> 32) as usize; //always drawn so pinning the first plant keeps the rest of the seed
+        let first_plant = pinned_first_plant.unwrap_or_else(|| first_plant_options[random_idx]);
+        let mut early_plants = plant_options.early_plants(first_plant)?;
         plant_order.push(first_plant);
         restrictions_data.unlocked_plants.insert(Unlockable::Peashooter);
         restrictions_data.unlocked_plants.insert(Unlockable::SunFlower);
@@ -2668,16 +2461,53 @@ impl RandomisationData {
         let mut predetermined_level_plants: FxHashMap<u8, (Unlockable, f32)> = HashMap::default();
         predetermined_level_plants.insert(1, (first_plant, 999.0));
         
-        let possible_solutions_part_1 = Self::get_solutions_1();
-        let possible_solutions_part_2 = Self::get_solutions_2();
-        let possible_solutions_part_3 = Self::get_solutions_3();
+        let mut planned_unlocks: Vec<(u8, Unlockable)> = Vec::new(); //slots with a planned plant but no planned level
+        for planned in options.plan.iter().flat_map(|plan| &plan.slots).filter(|planned| planned.slot > 1) {
+            match (planned.level, planned.unlock) {
+                (Some(level_idx), unlock) => {
+                    restrictions_data.planned_levels.insert(level_idx, planned.slot);
+                    if let Some(plant) = unlock {
+                        restrictions_data.unlocked_plants.insert(plant);
+                        predetermined_level_plants.insert(level_idx, (plant, 10.0));
+                        remaining_levels.retain(|level| *level != level_idx);
+                    }
+                }
+                (None, Some(plant)) => planned_unlocks.push((planned.slot, plant)),
+                (None, None) => (),
+            }
+            if let Some(plant) = planned.unlock {
+                early_plants.retain(|early_plant| *early_plant != plant);
+            }
+        }
+        for (slot, plant) in planned_unlocks { //a level is picked for these like for early plants, then it waits for its slot
+            let level_idx = ret.pick_level(&remaining_levels, &predetermined_level_plants, &blacklist_set, false, &mut plants_rng, params.odyssey_levels, seed)?;
+            let level_idx_idx = remaining_levels.binary_search(&(level_idx as u8)).unwrap();
+            remaining_levels.remove(level_idx_idx);
+            let restrictions_data = ret.restrictions_data.as_mut().unwrap();
+            restrictions_data.unlocked_plants.insert(plant);
+            restrictions_data.planned_levels.insert(level_idx as u8, slot);
+            predetermined_level_plants.insert(level_idx as u8, (plant, 10.0));
+        }
+        
+        for plant in early_plants {
+            let level_idx = ret.pick_level(&remaining_levels, &predetermined_level_plants, &blacklist_set, false, &mut plants_rng, params.odyssey_levels, seed)?;
+            let level_idx_idx = remaining_levels.binary_search(&(level_idx as u8)).unwrap();
+            remaining_levels.remove(level_idx_idx);
+            let restrictions_data = ret.restrictions_data.as_mut().unwrap();
+            restrictions_data.unlocked_plants.insert(plant);
+            restrictions_data.early_levels.push(level_idx as u8);
+            predetermined_level_plants.insert(level_idx as u8, (plant, 10.0));
+        }
+        
+        let [possible_solutions_part_1, possible_solutions_part_2, possible_solutions_part_3] = &RULES.get().unwrap().solutions;
         
-        ret.assign_solutions(possible_solutions_part_1, &mut predetermined_level_plants, &mut remaining_levels, &blacklist_set, &mut plants_rng, seed);
-        ret.assign_solutions(possible_solutions_part_2, &mut predetermined_level_plants, &mut remaining_levels, &blacklist_set, &mut plants_rng, seed);
-        ret.assign_solutions(possible_solutions_part_3, &mut predetermined_level_plants, &mut remaining_levels, &blacklist_set, &mut plants_rng, seed);
+        ret.assign_solutions(possible_solutions_part_1, &mut predetermined_level_plants, &mut remaining_levels, &blacklist_set, &mut plants_rng, params.odyssey_levels, seed)?;
+        ret.assign_solutions(possible_solutions_part_2, &mut predetermined_level_plants, &mut remaining_levels, &blacklist_set, &mut plants_rng, params.odyssey_levels, seed)?;
+        ret.assign_solutions(possible_solutions_part_3, &mut predetermined_level_plants, &mut remaining_levels, &blacklist_set, &mut plants_rng, params.odyssey_levels, seed)?;
         
-        println!("Chosen plant solutions: {:?}", predetermined_level_plants.values().collect::<Vec<_>>());
-        println!("Blacklist: {blacklist_set:?}");
+        ret.stats.first_plant = Some(first_plant);
+        ret.stats.chosen_solutions = predetermined_level_plants.values().copied().collect();
+        ret.stats.blacklist = blacklist_set.iter().copied().collect();
         
         let mut remaining_plants: Vec<Unlockable> = vec![
             Unlockable::CherryBomb,
@@ -2726,8 +2556,9 @@ impl RandomisationData {
         for (level, (plant, _)) in &predetermined_level_plants {
             forced_plants.insert(*plant, *level);
         }
+        let restrictions_data = ret.restrictions_data.as_ref().unwrap();
         for (i, unlockable) in remaining_plants.iter().enumerate() {
-            if forced_plants.contains_key(unlockable) {
+            if forced_plants.contains_key(unlockable) || restrictions_data.excluded_plants.contains(unlockable) {
                 to_remove.push(i);
             }
         }
@@ -2743,16 +2574,44 @@ impl RandomisationData {
         restrictions_data.unlocked_plants.insert(Unlockable::SunFlower);
         
         while !remaining_levels.is_empty() {
-            let level_idx = Self::pick_level(&mut ret, &remaining_levels, &predetermined_level_plants, &blacklist_set, true, &mut level_rng, seed);
+            let slot = ret.level_order.len() + 1;
+            let planned_levels = &mut ret.restrictions_data.as_mut().unwrap().planned_levels;
+            let planned_level = planned_levels.iter().find(|(_, planned_slot)| **planned_slot as usize == slot).map(|(level_idx, _)| *level_idx);
+            let level_idx = match planned_level {
+                Some(level_idx) => {
+                    planned_levels.remove(&level_idx);
+                    level_idx as usize
+                }
+                None => Self::pick_level(&mut ret, &remaining_levels, &predetermined_level_plants, &blacklist_set, true, &mut level_rng, params.odyssey_levels, seed)?,
+            };
             let level_idx_idx = remaining_levels.binary_search(&(level_idx as u8)).unwrap();
+            if let Some((_, rerolls)) = options.rerolls.iter().find(|(reroll_slot, _)| *reroll_slot as usize == slot) {
+                let blacklisted = blacklist_set.contains(&(level_idx as u32));
+                let rerolls = ret.reroll_level(level_idx, slot, *rerolls, seed, blacklisted, enum_variants, fuse_data, &params)?;
+                ret.rerolls.push((slot as u8, rerolls));
+            } else if planned_level.is_some() { //picked levels are already checked
+                let reasons = ret.is_level_possible(level_idx as u32, slot as u32 - 1, seed)?;
+                if ret.apply_reasons(level_idx as u8, reasons, slot as u32 - 1)?.is_none() {
+                    return Err(GenerationError::PlannedLevelImpossible(slot as u8, level_idx));
+                }
+            }
             let restrictions_data = ret.restrictions_data.as_mut().unwrap();
+            restrictions_data.early_levels.retain(|level| *level as usize != level_idx);
+            if let Some(modifications) = restrictions_data.level_modifications.remove(&(level_idx as u8)) {
+                ret.stats.hard_zombies += modifications.hard_zombies as u32;
+                ret.stats.bad_plants   += modifications.bad_plants as u32;
+                ret.stats.unaffordable += modifications.unaffordable as u32;
+            }
             
             if let Some(spawns) = restrictions_data.level_spawns.remove(&(level_idx as u8)) {
-                let actual_spawns = if let Some(spawns) = restrictions_data.modified_level_spawns.remove(&(level_idx as u8)) {
+                let mut actual_spawns = if let Some(spawns) = restrictions_data.modified_level_spawns.remove(&(level_idx as u8)) {
                     spawns
                 } else {
                     spawns
                 };
+                if restrictions_data.progressive_weights {
+                    Self::scale_spawns(&mut actual_spawns, ret.level_order.len());
+                }
                 let mut spawns_bitfield = vec![0; 16];
                 let mut weights_vec = vec![0; zombie_data.len() * 4];
                 for (idx, weight) in actual_spawns.iter() {
@@ -2768,11 +2627,11 @@ impl RandomisationData {
                     spawns.push(spawns_bitfield);
                 }
                 if ret.freqs.is_some() {
-                    let data = ret.compute_zombie_freq_data_cached(&actual_spawns, level_idx).unwrap();
+                    let data = ret.compute_zombie_freq_data_cached(&actual_spawns, level_idx)?;
                     unsafe { ret.freqs.as_mut().unwrap_unchecked() }.push(data.totals);
                 }
             } else {
-                unreachable!();
+                return Err(GenerationError::MissingLevelData(level_idx));
             }
             
             let restrictions_data = ret.restrictions_data.as_mut().unwrap();
@@ -2793,8 +2652,26 @@ impl RandomisationData {
                 if let Some(costs) = ret.costs.as_mut() {
                     costs.push(cs_vec);
                 }
+                if let Some(conveyors) = ret.conveyors.as_mut() {
+                    conveyors.push(restrictions_data.conveyor_pools.get(&(level_idx as u8)).map_or_else(Vec::new, |(weights, _)| weights.clone()));
+                }
+                if let Some(flag_counts) = ret.flag_counts.as_mut() {
+                    flag_counts.push(vec![restrictions_data.flag_counts.get(&(level_idx as u8)).copied().unwrap_or(0)]);
+                }
+                if let Some(scenes) = ret.scenes.as_mut() {
+                    scenes.push(vec![restrictions_data.scenes[level_idx - 1] as u8]);
+                }
+                if let Some(zombie_stats) = ret.zombie_stats.as_mut() {
+                    zombie_stats.push(restrictions_data.zombie_stats[level_idx - 1].clone());
+                }
+                if let Some(plant_damage) = ret.plant_damage.as_mut() {
+                    plant_damage.push(restrictions_data.plant_damage[level_idx - 1].clone());
+                }
+                if let Some(sun_production) = ret.sun_production.as_mut() {
+                    sun_production.push(restrictions_data.level_sun[level_idx - 1].clone());
+                }
             } else {
-                unreachable!();
+                return Err(GenerationError::MissingLevelData(level_idx));
             }
             
             remaining_levels.remove(level_idx_idx);
@@ -2822,7 +2699,12 @@ impl RandomisationData {
                         plant_choices.push((idx, *plant));
                     }
                 }
-                let idx = ((plants_rng.next_u32() as u64 * plant_choices.len() as u64) >> 32) as usize;
+                let idx = if plant_options.weighted {
+                    let progress = ret.level_order.len() as f32 / 45.;
+                    Self::weighted_plant_pick(&plant_choices, &restrictions_data.unlocked_plants, progress, plants_rng.next_u32())
+                } else {
+                    ((plants_rng.next_u32() as u64 * plant_choices.len() as u64) >> 32) as usize
+                };
                 let (rm_idx, plant) = plant_choices[idx];
                 plant_order.push(plant);
                 restrictions_data.unlocked_plants.insert(plant);
@@ -2836,13 +2718,890 @@ impl RandomisationData {
         for (plant, i) in plant_order.iter().zip(2..) {
             ret.plant_order[*plant as usize] = i;
         }
+        for planned in options.plan.iter().flat_map(|plan| &plan.slots) {
+            if let Some(plant) = planned.unlock.filter(|plant| ret.plant_order[*plant as usize] != planned.slot + 1) { //an upgrade can take its base plant's level
+                return Err(GenerationError::InvalidPlan(format!("Slot {} couldn't unlock {plant:?} with this seed", planned.slot)));
+            }
+        }
         
-        println!("Plant order: {plant_order:?}");
+        ret.stats.unlock_order = plant_order;
         
-        ret
+        Ok(ret)
     }
 }
 
-fn mul_from_u8(mul: u8) -> f32 {
+pub fn mul_from_u8(mul: u8) -> f32 {
     ((mul & 0x7F) as f32 / 127. + 1.) * if mul < 0x80 {0.5} else {1.}
 }
+
+fn mul_to_u8(mul: f32) -> u8 { //the highest byte with at most this multiplier, give or take rounding
+    if mul >= 1. {
+        0x80 + ((mul - 1.) * 127. + 1e-3).floor().min(127.) as u8
+    } else {
+        ((mul * 2. - 1.) * 127. + 1e-3).floor().max(0.) as u8
+    }
+}
+
+//the most sun that can be saved up by the end of each wave, from planting producers until some wave and saving everything after that
+//producer is the cost, cooldown and sun a second of the best sun producer available, if there is one
+fn banked_sun(sky_sun: f32, producer: Option<(f32, f32, f32)>, waves: usize) -> Vec<f32> { //indexed by wave, 0 is the start of the level
+    let seconds = WAVE_SECONDS as usize;
+    let (cost, cooldown, rate) = producer.unwrap_or((f32::INFINITY, 0., 0.));
+    let mut sun = STARTING_SUN;
+    let mut producers = 0;
+    let mut recharged = 0f32;
+    let mut stops = Vec::with_capacity(waves + 1); //sun and producers at the end of each wave when planting producers the whole time
+    stops.push((sun, producers));
+    for second in 0..waves * seconds {
+        if producers < MAX_PRODUCERS && recharged <= second as f32 && sun >= cost {
+            sun -= cost;
+            producers += 1;
+            recharged = second as f32 + cooldown;
+        }
+        sun += sky_sun + producers as f32 * rate;
+        if (second + 1) % seconds == 0 {
+            stops.push((sun, producers));
+        }
+    }
+    
+    (0..=waves).map(|wave| { //after stopping only the income is left
+        stops[..=wave].iter().enumerate()
+            .map(|(stop, (sun, producers))| sun + (wave - stop) as f32 * WAVE_SECONDS * (sky_sun + *producers as f32 * rate))
+            .fold(0., f32::max)
+    }).collect()
+}
+
+fn wave_budget(wave: isize) -> usize { //wavepoints spent on a wave, flag waves get double
+    (wave as usize * 5 / 3) * if wave % 10 == 0 {2} else {1}
+}
+
+//expected number of each zombie in a wave with a budget of r wavepoints, for every r up to max_budget, in rows of spawn_vec.len()
+//the chance of each pick only depends on the points left, so a wave with budget r picks one zombie and continues like a wave with the rest,
+//which lets every wave of a level share one table instead of running the whole pick process again
+//a row only depends on the zombies it can afford, so rows of an old table below the cheapest zombie added, removed or reweighted are reused
+#[allow(clippy::type_complexity)]
+fn expected_counts_table(spawn_vec: &[(u32, u32, u32)], max_budget: usize, reuse: Option<(&[(u32, u32, u32)], &[f64])>) -> Option<Vec<f64>> {
+    let len = spawn_vec.len();
+    if spawn_vec.iter().all(|(_, _, points)| *points == 0) {
+        return None;
+    }
+    
+    let mut table = vec![0f64; (max_budget + 1) * len];
+    let mut start = 1;
+    if let Some((old_spawn_vec, old_table)) = reuse {
+        let changed_points = spawn_vec.iter().filter(|zombie| !old_spawn_vec.contains(zombie))
+            .chain(old_spawn_vec.iter().filter(|zombie| !spawn_vec.contains(zombie)))
+            .map(|(_, _, points)| *points as usize)
+            .min()
+            .unwrap_or(usize::MAX);
+        start = changed_points.min(old_table.len() / old_spawn_vec.len()).clamp(1, max_budget + 1);
+        let old_columns: Vec<Option<usize>> = spawn_vec.iter().map(|(idx, _, _)| old_spawn_vec.iter().position(|(old_idx, _, _)| old_idx == idx)).collect();
+        for budget in 1..start {
+            for (dst, old_column) in table[budget * len..][..len].iter_mut().zip(&old_columns) {
+                if let Some(old_column) = old_column {
+                    *dst = old_table[budget * old_spawn_vec.len() + old_column];
+                }
+            }
+        }
+    }
+    
+    let mut affordable = 0;
+    let mut weight_sum = 0f64;
+    for budget in start..=max_budget {
+        while affordable < len && spawn_vec[affordable].2 as usize <= budget {
+            weight_sum += spawn_vec[affordable].1 as f64;
+            affordable += 1;
+        }
+        let (done, rest) = table.split_at_mut(budget * len);
+        let row = &mut rest[..len];
+        for (i, (_, weight, points)) in spawn_vec.iter().take(affordable).enumerate() {
+            let chance = *weight as f64 / weight_sum;
+            row[i] += chance;
+            if budget > *points as usize {
+                for (dst, src) in row.iter_mut().zip(&done[(budget - *points as usize) * len..][..len]) {
+                    *dst += chance * src;
+                }
+            }
+        }
+    }
+    Some(table)
+}
+
+#[cfg(test)]
+fn compute_freq_for_wave(spawn_vec: &[(u32, u32, u32)], wave: isize) -> Option<Vec<f64>> { //the original model, which runs the whole pick process for every wave
+    let mut wavepoints_max = 0;
+    
+    for (_, _, points) in spawn_vec {
+        wavepoints_max = isize::max(*points as isize, wavepoints_max);
+    }
+    
+    if wavepoints_max == 0 {
+        return None;
+    }
+    
+    let mut spawns_lut: Vec<Vec<f64>> = Vec::with_capacity(wavepoints_max as usize - 1);
+    
+    for i in 1 ..= wavepoints_max {
+        let mut sum = 0f64;
+        let mut vec: Vec<f64> = Vec::new();
+        for (_, weight, _) in spawn_vec.iter().take_while(|(_, _, points)| *points as isize <= i) {
+            sum += *weight as f64;
+            vec.push(*weight as f64);
+        }
+        
+        for weight in vec.iter_mut() {
+            *weight /= sum;
+        }
+        spawns_lut.push(vec);
+    }
+    
+    let total_wavepoints = wave_budget(wave);
+    let mut zombie_odds: Vec<f64> = vec![0f64; spawn_vec.len()];
+    let mut wavepoint_odds_array = vec![0f64; total_wavepoints];
+    wavepoint_odds_array[total_wavepoints - 1] = 1f64;
+    
+    for remaining_points in (1..=total_wavepoints).rev() {
+        let chance_1 = wavepoint_odds_array[remaining_points - 1];
+        for (chance_2, ((_, _, choice_wavepoints), choice_odds)) in
+            spawns_lut[usize::min(remaining_points, spawns_lut.len()) - 1]
+            .iter()
+            .zip(spawn_vec.iter().zip(zombie_odds.iter_mut()))
+        {
+            let chance = chance_1 * *chance_2;
+            *choice_odds += chance;
+            if remaining_points > *choice_wavepoints as usize {
+                wavepoint_odds_array[remaining_points - *choice_wavepoints as usize - 1] += chance;
+            }
+        }
+    }
+    
+    Some(zombie_odds)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs::{create_dir_all, read_to_string, write}, mem::transmute, path::PathBuf, time::Instant};
+    
+    use fxhash::FxHashMap;
+    
+    use crate::{data::{init_defaults, LevelType, Unlockable, ZombieFlags, ZombieLanes, ZombieType, LEVEL_DATA, ZOMBIE_DATA}, headless::describe, plan::Plan, plant_options::PlantOptions, rules::{Rules, DEFAULT_RULES, RULES}, util::hash_str};
+    use super::{banked_sun, compute_freq_for_wave, mul_from_u8, mul_to_u8, FrequencyData, GenerationError, GenerationOptions, ImpossibleReason, LevelPlants, Problem, RandomisationData, SolutionEntry, SKY_SUN, STARTING_SUN, WAVE_SECONDS, ZOMBIE_STAT_STRIDE};
+    
+    const SEEDS: [&str; 3] = ["0", "fusion", "14159265358979323846"];
+    
+    //a stand in for the enums in a dump, every zombie and menu plant gets an id and some fusions are made up on top
+    fn synthetic_enum_variants() -> FxHashMap<String, u64> {
+        let mut enum_variants: FxHashMap<String, u64> = HashMap::default();
+        
+        for i in 0..=ZombieType::IceClawZombie as u8 {
+            let zombie: ZombieType = unsafe { transmute(i) };
+            enum_variants.insert(format!("ZombieType::{zombie:?}"), (i as u64 * 7) % 107); //scrambled so sorting by id matters, 107 is prime so the ids stay unique
+        }
+        for i in 0..=Unlockable::CobCannon as u8 {
+            let plant: Unlockable = unsafe { transmute(i) };
+            enum_variants.insert(format!("PlantType::{plant:?}"), i as u64);
+        }
+        for i in 0..24 {
+            enum_variants.insert(format!("PlantType::Fusion{i}"), 1000 + i);
+        }
+        enum_variants.insert("PlantType::Nothing".to_string(), u64::MAX);
+        
+        enum_variants
+    }
+    
+    fn synthetic_fuse_map() -> FxHashMap<u32,[u32;2]> {
+        let mut fuse_map: FxHashMap<u32,[u32;2]> = HashMap::default();
+        
+        for i in 0..20 {
+            fuse_map.insert(1000 + i, [i, (i * 3 + 5) % 41]);
+        }
+        for i in 20..24 {
+            fuse_map.insert(1000 + i, [1000 + i - 20, 1000 + i - 19]); //fusions of fusions
+        }
+        
+        fuse_map
+    }
+    
+    fn setup() -> (FxHashMap<String, u64>, FxHashMap<u32,[u32;2]>) { //the game data and rules every generation test starts from
+        let enum_variants = synthetic_enum_variants();
+        init_defaults(&enum_variants);
+        RULES.get_or_init(|| Rules::parse(DEFAULT_RULES).unwrap());
+        (enum_variants, synthetic_fuse_map())
+    }
+    
+    fn check_snapshot(name: &str, output: &str) {
+        let dir = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/src/snapshots"));
+        let path = dir.join(format!("{name}.txt"));
+        
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            create_dir_all(&dir).unwrap();
+            write(&path, output).unwrap();
+            return;
+        }
+        
+        let expected = read_to_string(&path).unwrap_or_else(|_| panic!("Missing snapshot {}, run with UPDATE_SNAPSHOTS=1 to create it", path.display()));
+        assert!(expected == output, "{name} no longer matches its snapshot, seeds players have shared would change. If this is intended, rerun with UPDATE_SNAPSHOTS=1");
+    }
+    
+    #[test]
+    fn no_restrictions_snapshots() {
+        let (enum_variants, fuse_map) = setup();
+        
+        for seed in SEEDS {
+            let rand_data = RandomisationData::no_restrictions(hash_str(seed), &GenerationOptions::default(), &enum_variants, &fuse_map).unwrap();
+            check_snapshot(&format!("no_restrictions_{seed}"), &describe(&rand_data));
+        }
+    }
+    
+    #[test]
+    fn restrictions_snapshots() {
+        let (enum_variants, fuse_map) = setup();
+        
+        for seed in SEEDS {
+            let rand_data = RandomisationData::restrictions(hash_str(seed), &GenerationOptions::default(), &enum_variants, &fuse_map).unwrap();
+            check_snapshot(&format!("restrictions_{seed}"), &describe(&rand_data));
+        }
+    }
+    
+    #[test]
+    fn plant_options() {
+        let (enum_variants, fuse_map) = setup();
+        
+        let options = GenerationOptions {
+            plant_options: PlantOptions {
+                first_plant:  Some(Unlockable::Jalapeno),
+                early_plants: vec![Unlockable::GloomShroom, Unlockable::Pot],
+                early_levels: 6,
+                excluded:     vec![Unlockable::Chomper, Unlockable::Caltrop],
+                weighted:     false,
+            },
+            ..Default::default()
+        };
+        for seed in SEEDS {
+            let rand_data = RandomisationData::restrictions(hash_str(seed), &options, &enum_variants, &fuse_map).unwrap();
+            assert_eq!(rand_data.stats.unlock_order[0], Unlockable::Jalapeno);
+            for plant in [Unlockable::FumeShroom, Unlockable::GloomShroom, Unlockable::Pot] { //fume shroom is needed for gloom shroom
+                assert!(rand_data.plant_order[plant as usize] <= 7, "{plant:?} was unlocked after the first 6 levels"); //unlocked by slot n means a value of n + 2
+            }
+            for plant in [Unlockable::Chomper, Unlockable::Caltrop, Unlockable::SpikeRock] { //spike rock needs caltrop
+                assert_eq!(rand_data.plant_order[plant as usize], 0xFF);
+            }
+        }
+        
+        let bad_options = GenerationOptions {
+            plant_options: PlantOptions {
+                first_plant: Some(Unlockable::Chomper),
+                excluded:    vec![Unlockable::Chomper],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(RandomisationData::restrictions(0, &bad_options, &enum_variants, &fuse_map).is_err());
+    }
+    
+    #[test]
+    fn weighted_plants() {
+        let (enum_variants, fuse_map) = setup();
+        let usefulness = &RULES.get().unwrap().plant_usefulness;
+        
+        //how many slots later weak plants are unlocked than useful ones on average
+        let unlock_gap = |weighted: bool| -> f32 {
+            let options = GenerationOptions {
+                plant_options: PlantOptions {weighted, ..Default::default()},
+                ..Default::default()
+            };
+            let (mut useful, mut weak) = (Vec::new(), Vec::new());
+            for seed in 0..30 {
+                let rand_data = RandomisationData::restrictions(seed, &options, &enum_variants, &fuse_map).unwrap();
+                for plant in (Unlockable::CherryBomb as u8..=Unlockable::CobCannon as u8).filter_map(Unlockable::from_u8) {
+                    let slot = rand_data.plant_order[plant as usize];
+                    assert_ne!(slot, 0xFF, "{plant:?} was never unlocked");
+                    if usefulness[plant as usize] >= 7. {
+                        useful.push(slot as f32);
+                    } else if usefulness[plant as usize] <= 3. {
+                        weak.push(slot as f32);
+                    }
+                }
+            }
+            weak.iter().sum::<f32>() / weak.len() as f32 - useful.iter().sum::<f32>() / useful.len() as f32
+        };
+        assert!(unlock_gap(true) > unlock_gap(false) + 2.);
+        
+        let options = GenerationOptions {
+            plant_options: PlantOptions {weighted: true, ..Default::default()},
+            ..Default::default()
+        };
+        for seed in SEEDS {
+            let rand_data = RandomisationData::restrictions(hash_str(seed), &options, &enum_variants, &fuse_map).unwrap();
+            let again = RandomisationData::restrictions(hash_str(seed), &options, &enum_variants, &fuse_map).unwrap();
+            assert_eq!(rand_data.plant_order, again.plant_order);
+        }
+    }
+    
+    #[test]
+    fn rules_hash() {
+        let built_in = Rules::parse(DEFAULT_RULES).unwrap().hash;
+        let reformatted = format!("//a comment\n{}\n\n", DEFAULT_RULES.replace("    ", "\t"));
+        assert_eq!(Rules::parse(&reformatted).unwrap().hash, built_in);
+        let changed = DEFAULT_RULES.replacen("weight: 2.0", "weight: 3.0", 1);
+        assert_ne!(Rules::parse(&changed).unwrap().hash, built_in);
+    }
+    
+    #[test]
+    fn conveyor_pools() {
+        let (enum_variants, fuse_map) = setup();
+        let level_data = LEVEL_DATA.get().unwrap();
+        
+        let mut pools = 0;
+        for seed in SEEDS {
+            let rand_data = RandomisationData::restrictions(hash_str(seed), &GenerationOptions {conveyors: true, ..Default::default()}, &enum_variants, &fuse_map).unwrap();
+            let conveyors = rand_data.conveyors.as_ref().unwrap();
+            for (slot, level) in rand_data.level_order.iter().enumerate() {
+                let level = &level_data[*level as usize - 1];
+                assert!(conveyors[slot].is_empty() || level.conveyor_plants.is_some());
+                assert_eq!(conveyors[slot].iter().any(|weight| *weight > 0), !conveyors[slot].is_empty()); //a level without a usable pool is left empty so the game picks its normal plants
+                pools += !conveyors[slot].is_empty() as usize;
+                if level.level_type == LevelType::Roof && !conveyors[slot].is_empty() {
+                    assert!(conveyors[slot][Unlockable::Pot as usize] > 0);
+                }
+            }
+        }
+        assert!(pools > 0);
+    }
+    
+    #[test]
+    fn flag_counts() {
+        let (enum_variants, fuse_map) = setup();
+        let level_data = LEVEL_DATA.get().unwrap();
+        
+        for seed in SEEDS {
+            let rand_data = RandomisationData::restrictions(hash_str(seed), &GenerationOptions {flag_counts: true, ..Default::default()}, &enum_variants, &fuse_map).unwrap();
+            for level in &rand_data.level_order {
+                let Some(default_flags) = level_data[*level as usize - 1].flags else {
+                    continue;
+                };
+                let flags = rand_data.flag_count(*level as usize).unwrap();
+                assert!((1..=4).contains(&flags));
+                assert!(flags.abs_diff(default_flags) <= 1);
+                if level_data[*level as usize - 1].level_type == LevelType::Roof && default_flags > 1 {
+                    assert!(flags > 1);
+                }
+                if *level == 1 {
+                    assert_eq!(flags, default_flags);
+                }
+            }
+        }
+    }
+    
+    #[test]
+    fn roof_pots() {
+        let (enum_variants, fuse_map) = setup();
+        let level_data = LEVEL_DATA.get().unwrap();
+        let find_level = |level_type: LevelType, flags: u8| (2..=45u32).find(|level| {
+            let level = &level_data[*level as usize - 1];
+            level.level_type == level_type && level.flags == Some(flags) && level.conveyor_plants.is_none()
+        }).unwrap();
+        let needs_pot = |rand_data: &mut RandomisationData, level: u32| rand_data.is_level_possible(level, 10, 3).unwrap().iter().any(|reason| matches!(reason, ImpossibleReason::NoPot));
+        
+        let mut rand_data = RandomisationData::restrictions(3, &GenerationOptions::default(), &enum_variants, &fuse_map).unwrap();
+        let (pot_level, roof_level, day_level) = (find_level(LevelType::Roof, 1), find_level(LevelType::Roof, 2), find_level(LevelType::Day, 1));
+        let normal_zombie = ZOMBIE_DATA.get().unwrap().iter().position(|zombie| zombie.zombie_type == ZombieType::NormalZombie).unwrap() as u32;
+        for level in [pot_level, roof_level, day_level] { //placed levels get their spawns and plants back so they can be checked again
+            let slot = rand_data.level_order.iter().position(|placed| *placed as u32 == level).unwrap();
+            let plants = LevelPlants {
+                menu: rand_data.cooldowns.as_ref().unwrap()[slot].iter().copied().zip(rand_data.costs.as_ref().unwrap()[slot].iter().copied()).collect(),
+                all: rand_data.firerates.as_ref().unwrap()[slot].clone(),
+            };
+            let restrictions_data = rand_data.restrictions_data.as_mut().unwrap();
+            restrictions_data.level_spawns.insert(level as u8, vec![(normal_zombie, 4000)]);
+            restrictions_data.level_plants.insert(level as u8, plants);
+        }
+        let restrictions_data = rand_data.restrictions_data.as_mut().unwrap();
+        for plant in RULES.get().unwrap().all_solutions.get(&Problem::Roof).unwrap().iter().flatten() {
+            restrictions_data.unlocked_plants.remove(plant);
+        }
+        
+        assert!(!needs_pot(&mut rand_data, pot_level));
+        assert!(needs_pot(&mut rand_data, roof_level));
+        let restrictions_data = rand_data.restrictions_data.as_mut().unwrap();
+        restrictions_data.flag_counts.insert(roof_level as u8, 1);
+        restrictions_data.scenes[day_level as usize - 1] = LevelType::Roof;
+        assert!(needs_pot(&mut rand_data, roof_level));
+        assert!(needs_pot(&mut rand_data, day_level));
+    }
+    
+    #[test]
+    fn scenes() {
+        let (enum_variants, fuse_map) = setup();
+        let level_data = LEVEL_DATA.get().unwrap();
+        let zombie_data = ZOMBIE_DATA.get().unwrap();
+        
+        for seed in SEEDS {
+            let rand_data = RandomisationData::restrictions(hash_str(seed), &GenerationOptions {scenes: true, ..Default::default()}, &enum_variants, &fuse_map).unwrap();
+            let spawns = rand_data.spawns.as_ref().unwrap();
+            for (slot, level) in rand_data.level_order.iter().enumerate() {
+                let scene = rand_data.scene(*level as usize);
+                if *level == 1 || level_data[*level as usize - 1].conveyor_plants.is_some() {
+                    assert_eq!(scene, level_data[*level as usize - 1].level_type);
+                }
+                if !scene.has_water() {
+                    for (i, zombie) in zombie_data.iter().enumerate() {
+                        if let ZombieLanes::Water = zombie.allowed_lanes {
+                            assert_eq!(spawns[slot][i >> 3] & 1 << (i & 7), 0);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    
+    #[test]
+    fn zombie_stats() {
+        let (enum_variants, fuse_map) = setup();
+        let zombie_data = ZOMBIE_DATA.get().unwrap();
+        
+        for seed in SEEDS {
+            let rand_data = RandomisationData::restrictions(hash_str(seed), &GenerationOptions {zombie_stats: true, ..Default::default()}, &enum_variants, &fuse_map).unwrap();
+            let zombie_stats = rand_data.zombie_stats.as_ref().unwrap();
+            assert!(zombie_stats[0].iter().all(|byte| *byte == 0x80));
+            for (slot, level) in rand_data.level_order.iter().enumerate() {
+                assert_eq!(zombie_stats[slot].len(), ZOMBIE_STAT_STRIDE * 3);
+                for (i, zombie) in zombie_data.iter().enumerate() {
+                    let flags = rand_data.zombie_flags(*level as usize, i);
+                    assert!(flags.contains(zombie.flags));
+                    if mul_from_u8(zombie_stats[slot][i]) >= 1.5 {
+                        assert!(flags.contains(ZombieFlags::HIGH_HEALTH));
+                    } else {
+                        assert!(flags == zombie.flags);
+                    }
+                }
+            }
+        }
+    }
+    
+    #[test]
+    fn plant_stats() {
+        let (enum_variants, fuse_map) = setup();
+        let (plant_map, plant_ids, rev_map) = RandomisationData::get_plant_map_and_ids(&enum_variants);
+        let options = GenerationOptions {plant_damage: true, sun_production: true, ..Default::default()};
+        
+        for seed in SEEDS {
+            for rand_data in [
+                RandomisationData::no_restrictions(hash_str(seed), &options, &enum_variants, &fuse_map).unwrap(),
+                RandomisationData::restrictions(hash_str(seed), &options, &enum_variants, &fuse_map).unwrap(),
+            ] {
+                for (table, protected) in [(&rand_data.plant_damage, "Peashooter"), (&rand_data.sun_production, "SunFlower")] {
+                    let table = table.as_ref().unwrap();
+                    assert!(table[0].iter().all(|byte| *byte == 0x80));
+                    for stats in table {
+                        assert_eq!(stats.len(), plant_ids.len());
+                        assert!(stats[*plant_map.get(protected).unwrap() as usize] >= 0x80);
+                        for i in 0..20 { //fusions of two plain plants
+                            let [a, b] = fuse_map[&(1000 + i)];
+                            let (a, b) = (stats[rev_map[&a] as usize] as u32, stats[rev_map[&b] as usize] as u32);
+                            assert_eq!(stats[rev_map[&(1000 + i)] as usize] as u32, (a + b) >> 1);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    
+    #[test]
+    fn fusions() {
+        let (enum_variants, fuse_map) = setup();
+        let (_, plant_ids, rev_map) = RandomisationData::get_plant_map_and_ids(&enum_variants);
+        let options = GenerationOptions {fusions: true, plant_damage: true, ..Default::default()};
+        let mut original: Vec<[u32;2]> = (1000..1020).map(|fusion| fuse_map[&fusion]).collect();
+        original.sort_unstable();
+        
+        assert!(RandomisationData::randomise_fusions(0, false, &fuse_map).is_none());
+        for seed in SEEDS {
+            let fusions = RandomisationData::randomise_fusions(hash_str(seed), true, &fuse_map).unwrap();
+            assert_eq!(fusions.len(), fuse_map.len());
+            let mut shuffled: Vec<[u32;2]> = (1000..1020).map(|fusion| fusions[&fusion]).collect();
+            shuffled.sort_unstable();
+            assert_eq!(shuffled, original);
+            for fusion in 1020..1024 { //fusions of fusions keep their recipes
+                assert_eq!(fusions[&fusion], fuse_map[&fusion]);
+            }
+            
+            for rand_data in [
+                RandomisationData::no_restrictions(hash_str(seed), &options, &enum_variants, &fuse_map).unwrap(),
+                RandomisationData::restrictions(hash_str(seed), &options, &enum_variants, &fuse_map).unwrap(),
+            ] {
+                assert_eq!(rand_data.fusions.as_ref(), Some(&fusions));
+                for stats in rand_data.plant_damage.as_ref().unwrap() {
+                    assert_eq!(stats.len(), plant_ids.len());
+                    for fusion in 1000..1020 { //damage follows the shuffled recipes
+                        let [a, b] = fusions[&fusion];
+                        let (a, b) = (stats[rev_map[&a] as usize] as u32, stats[rev_map[&b] as usize] as u32);
+                        assert_eq!(stats[rev_map[&fusion] as usize] as u32, (a + b) >> 1);
+                    }
+                }
+            }
+        }
+    }
+    
+    #[test]
+    fn progressive_weights() {
+        let (enum_variants, fuse_map) = setup();
+        let zombie_data = ZOMBIE_DATA.get().unwrap();
+        let options = GenerationOptions {progressive_weights: true, ..Default::default()};
+        
+        for slot in 2..45 {
+            let (normal, elite) = RandomisationData::slot_weight_scaling(slot);
+            let (prev_normal, prev_elite) = RandomisationData::slot_weight_scaling(slot - 1);
+            assert!(normal > prev_normal && elite > prev_elite);
+        }
+        
+        for seed in SEEDS {
+            let plain = RandomisationData::no_restrictions(hash_str(seed), &GenerationOptions::default(), &enum_variants, &fuse_map).unwrap();
+            let scaled = RandomisationData::no_restrictions(hash_str(seed), &options, &enum_variants, &fuse_map).unwrap();
+            assert_eq!(plain.level_order, scaled.level_order);
+            let scaling = scaled.weight_scaling.as_ref().unwrap();
+            for (slot, (plain_weights, scaled_weights)) in plain.weights.as_ref().unwrap().iter().zip(scaled.weights.as_ref().unwrap()).enumerate().skip(1) {
+                for (i, (plain_weight, scaled_weight)) in plain_weights.chunks_exact(4).zip(scaled_weights.chunks_exact(4)).enumerate() {
+                    let plain_weight = i32::from_le_bytes(plain_weight.try_into().unwrap()) as f32;
+                    let scaled_weight = i32::from_le_bytes(scaled_weight.try_into().unwrap()) as f32;
+                    let mul = match (i, zombie_data[i].flags.contains(ZombieFlags::IS_ELITE)) {
+                        (0, _)    => 1.,
+                        (_, true) => scaling[slot].1,
+                        _         => scaling[slot].0,
+                    };
+                    assert!((scaled_weight - (plain_weight * mul).round().max(1.)).abs() <= 1., "zombie {i} in slot {slot}");
+                }
+            }
+            
+            let rand_data = RandomisationData::restrictions(hash_str(seed), &options, &enum_variants, &fuse_map).unwrap();
+            assert_eq!(rand_data.weight_scaling.as_ref().unwrap().len(), 45);
+        }
+    }
+    
+    #[test]
+    fn parallel_checks() {
+        let (enum_variants, fuse_map) = setup();
+        
+        for options in [
+            GenerationOptions::default(),
+            GenerationOptions {scenes: true, zombie_stats: true, progressive_weights: true, ..Default::default()},
+        ] {
+            for seed in SEEDS {
+                let sequential = RandomisationData::restrictions(hash_str(seed), &GenerationOptions {threads: 1, ..options.clone()}, &enum_variants, &fuse_map).unwrap();
+                let parallel = RandomisationData::restrictions(hash_str(seed), &GenerationOptions {threads: 4, ..options.clone()}, &enum_variants, &fuse_map).unwrap();
+                assert_eq!(describe(&sequential), describe(&parallel));
+                assert_eq!(sequential.stats.hard_zombies, parallel.stats.hard_zombies);
+                assert_eq!(sequential.stats.bad_plants, parallel.stats.bad_plants);
+                for placed in [sequential.stats.hard_zombies, sequential.stats.bad_plants, sequential.stats.unaffordable] {
+                    assert!(placed <= 44); //only placed levels are counted
+                }
+            }
+        }
+    }
+    
+    struct SampleLevel {
+        spawn_vec: Vec<(u32, u32, u32)>, //sorted by points like compute_zombie_freq_data expects
+        level:     usize,
+        flags:     u8,
+    }
+    
+    fn sample_levels() -> Vec<SampleLevel> { //every level of a few seeds
+        let (enum_variants, fuse_map) = setup();
+        let zombie_data = ZOMBIE_DATA.get().unwrap();
+        
+        let mut ret = Vec::new();
+        for seed in SEEDS {
+            let rand_data = RandomisationData::no_restrictions(hash_str(seed), &GenerationOptions::default(), &enum_variants, &fuse_map).unwrap();
+            for (slot, level) in rand_data.level_order.iter().enumerate() {
+                let Ok(flags) = rand_data.flag_count(*level as usize) else {
+                    continue;
+                };
+                let (spawns, weights) = (&rand_data.spawns.as_ref().unwrap()[slot], &rand_data.weights.as_ref().unwrap()[slot]);
+                let mut spawn_vec = Vec::new();
+                for (i, bytes) in weights.chunks_exact(4).enumerate() {
+                    if spawns[i >> 3] & (1 << (i & 7)) != 0 {
+                        spawn_vec.push((i as u32, u32::from_le_bytes(bytes.try_into().unwrap()), zombie_data[i].default_points));
+                    }
+                }
+                spawn_vec.sort_by_key(|(_, _, points)| *points);
+                ret.push(SampleLevel {spawn_vec, level: *level as usize, flags});
+            }
+        }
+        ret
+    }
+    
+    fn reference_raw_averages(spawn_vec: &[(u32, u32, u32)], flags: u8) -> Vec<f32> { //the per wave model the expected counts table replaced
+        let zombie_data = ZOMBIE_DATA.get().unwrap();
+        let pre_10: Vec<usize> = (0..spawn_vec.len()).filter(|i| !zombie_data[spawn_vec[*i].0 as usize].flags.contains(ZombieFlags::IS_ELITE)).collect();
+        let spawn_vec_pre_10: Vec<(u32, u32, u32)> = pre_10.iter().map(|i| spawn_vec[*i]).collect();
+        
+        let mut ret = vec![0f32; flags as usize * 10 * spawn_vec.len()];
+        for wave in 1..=flags as isize * 10 {
+            let row = &mut ret[(wave as usize - 1) * spawn_vec.len()..][..spawn_vec.len()];
+            if wave < 10 {
+                for (freq, i) in compute_freq_for_wave(&spawn_vec_pre_10, wave).unwrap().into_iter().zip(&pre_10) {
+                    row[*i] = freq as f32;
+                }
+            } else {
+                for (dst, freq) in row.iter_mut().zip(compute_freq_for_wave(spawn_vec, wave).unwrap()) {
+                    *dst = freq as f32;
+                }
+            }
+        }
+        ret
+    }
+    
+    #[test]
+    fn wave_model() {
+        for SampleLevel {spawn_vec, level, flags} in sample_levels() {
+            let freq_data = RandomisationData::compute_zombie_freq_data(&spawn_vec, level, flags, None).unwrap();
+            let reference = reference_raw_averages(&spawn_vec, flags);
+            assert_eq!(freq_data.raw_averages.len(), reference.len());
+            for (freq, expected) in freq_data.raw_averages.iter().zip(&reference) {
+                assert!((freq - expected).abs() <= 1e-5 * expected.max(1.), "level {level}: {freq} != {expected}");
+            }
+            for (i, (id, _, _)) in spawn_vec.iter().enumerate() {
+                let first_flag_total: f32 = reference.iter().skip(i).step_by(spawn_vec.len()).take(9).sum();
+                assert!((freq_data.first_flag_totals[id] - first_flag_total).abs() <= 1e-4);
+            }
+        }
+    }
+    
+    fn tweak_weights(spawn_vec: &[(u32, u32, u32)]) -> Vec<(u32, u32, u32)> { //like a HardZombies change, lowers the most expensive zombie and removes the next one
+        let mut ret = spawn_vec.to_vec();
+        if let Some((_, weight, _)) = ret.last_mut() {
+            *weight = (*weight / 2).max(1);
+        }
+        if ret.len() > 2 {
+            ret.remove(ret.len() - 2);
+        }
+        ret
+    }
+    
+    #[test]
+    fn wave_model_reuse() {
+        for SampleLevel {spawn_vec, level, flags} in sample_levels() {
+            let old = RandomisationData::compute_zombie_freq_data(&spawn_vec, level, flags, None).unwrap();
+            let tweaked = tweak_weights(&spawn_vec);
+            let fresh  = RandomisationData::compute_zombie_freq_data(&tweaked, level, flags, None).unwrap();
+            let reused = RandomisationData::compute_zombie_freq_data(&tweaked, level, flags, Some(&old.counts)).unwrap();
+            for (freq, expected) in reused.raw_averages.iter().zip(&fresh.raw_averages) {
+                assert!((freq - expected).abs() <= 1e-6 * expected.max(1.), "level {level}: {freq} != {expected}");
+            }
+            let same = RandomisationData::compute_zombie_freq_data(&spawn_vec, level, flags, Some(&old.counts)).unwrap();
+            assert_eq!(same.raw_averages, old.raw_averages);
+        }
+    }
+    
+    #[test]
+    #[ignore] //cargo test --release wave_model_benchmark -- --ignored --nocapture
+    fn wave_model_benchmark() {
+        let samples = sample_levels();
+        let runs = 20;
+        
+        let start = Instant::now();
+        for _ in 0..runs {
+            for sample in &samples {
+                reference_raw_averages(&sample.spawn_vec, sample.flags);
+            }
+        }
+        let per_wave = start.elapsed();
+        
+        let start = Instant::now();
+        for _ in 0..runs {
+            for sample in &samples {
+                RandomisationData::compute_zombie_freq_data(&sample.spawn_vec, sample.level, sample.flags, None).unwrap();
+            }
+        }
+        let table = start.elapsed();
+        
+        let olds: Vec<FrequencyData> = samples.iter().map(|sample| RandomisationData::compute_zombie_freq_data(&sample.spawn_vec, sample.level, sample.flags, None).unwrap()).collect();
+        let tweaked: Vec<Vec<(u32, u32, u32)>> = samples.iter().map(|sample| tweak_weights(&sample.spawn_vec)).collect();
+        let start = Instant::now();
+        for _ in 0..runs {
+            for ((sample, old), spawn_vec) in samples.iter().zip(&olds).zip(&tweaked) {
+                RandomisationData::compute_zombie_freq_data(spawn_vec, sample.level, sample.flags, Some(&old.counts)).unwrap();
+            }
+        }
+        let reused = start.elapsed();
+        
+        println!("{} levels x {runs}: per wave model {per_wave:?}, expected counts table {table:?}, after a weight change {reused:?} (including the summaries)", samples.len());
+    }
+    
+    #[test]
+    fn plant_throughput() {
+        let plants = |cooldown: u8, cost: u8| LevelPlants {menu: vec![(cooldown, cost); 41], all: Vec::new()};
+        let entry = |plants: &[Unlockable], uses: Option<u8>| SolutionEntry {uses, plants: plants.iter().copied().collect(), ..Default::default()};
+        let normal = plants(0x80, 0x80);
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-5;
+        
+        //a peashooter recharges WAVE_SECONDS / 7.5 times a wave, so it keeps up with that many zombies and no more
+        let peashooter = entry(&[Unlockable::Peashooter], Some(1));
+        let mut usage = [0f32; 41];
+        assert_eq!(peashooter.answer_threat(&normal, &mut usage, 2.), 0.);
+        assert!(close(usage[Unlockable::Peashooter as usize], 2. * 7.5 / WAVE_SECONDS));
+        
+        //solutions sharing a plant share its recharges
+        let torchwood = entry(&[Unlockable::Peashooter, Unlockable::TorchWood], Some(1));
+        assert!(close(torchwood.compute_solution_cooldown(&normal, &usage), 7.5 / (1. - 2. * 7.5 / WAVE_SECONDS)));
+        assert!(close(torchwood.answer_threat(&normal, &mut usage, 2.), 4. - WAVE_SECONDS / 7.5));
+        assert_eq!(peashooter.compute_solution_cooldown(&normal, &usage), f32::INFINITY);
+        assert_eq!(peashooter.answer_threat(&normal, &mut usage, 1.), 1.);
+        
+        //the cooldown byte slows recharges down, the cost byte doesn't
+        let mut usage = [0f32; 41];
+        assert!(close(peashooter.answer_threat(&plants(0xFF, 0x80), &mut usage, 2.), 2. - WAVE_SECONDS / 15.));
+        let mut usage = [0f32; 41];
+        assert_eq!(peashooter.answer_threat(&plants(0x80, 0xFF), &mut usage, 2.), 0.);
+        
+        //every copy of a plant needs its own recharge, and each extra use stretches them
+        let mut usage = [0f32; 41];
+        let doom = entry(&[Unlockable::DoomShroom, Unlockable::DoomShroom], Some(1));
+        assert!(close(doom.answer_threat(&normal, &mut usage, 1.), 1. - WAVE_SECONDS / 100.));
+        assert!(close(entry(&[Unlockable::DoomShroom], Some(2)).compute_solution_cooldown(&normal, &[0.; 41]), 25.));
+        
+        //plants that stay on the lawn never need replanting
+        let mut usage = [0f32; 41];
+        let wallnut = entry(&[Unlockable::WallNut], None);
+        assert_eq!(wallnut.answer_threat(&normal, &mut usage, 100.), 0.);
+        assert_eq!(wallnut.compute_solution_cooldown(&normal, &usage), 0.);
+        assert!(usage.iter().all(|used| *used == 0.));
+    }
+    
+    #[test]
+    fn sun_economy() {
+        for byte in 0..=255u8 {
+            assert!(mul_from_u8(mul_to_u8(mul_from_u8(byte))) == mul_from_u8(byte));
+        }
+        for mul in [0.3f32, 0.5, 0.77, 1., 1.3, 2., 2.5] {
+            let byte = mul_to_u8(mul);
+            assert!(mul_from_u8(byte) <= mul.max(0.5) && (byte == 0xFF || mul_from_u8(byte + 1) > mul));
+        }
+        
+        assert!(banked_sun(0., None, 20).iter().all(|sun| *sun == STARTING_SUN));
+        let sky_only = banked_sun(SKY_SUN, None, 20);
+        for (wave, sun) in sky_only.iter().enumerate() {
+            assert_eq!(*sun, STARTING_SUN + wave as f32 * WAVE_SECONDS * SKY_SUN);
+        }
+        
+        //planting sunflowers pays off later on, and is never worse than saving since the player can choose not to
+        let sunflowers = banked_sun(SKY_SUN, Some((50., 7.5, 25. / 24.)), 20);
+        assert!(sunflowers.iter().zip(&sky_only).all(|(with, without)| with >= without));
+        assert!(sunflowers[20] > sky_only[20] * 2.);
+        assert!(sunflowers.windows(2).all(|pair| pair[1] >= pair[0]));
+        
+        //at night sunflowers are the only way to get sun
+        let night = banked_sun(0., Some((50., 7.5, 25. / 24.)), 20);
+        assert!(night[20] > night[1]);
+        assert!(banked_sun(0., Some((150., 7.5, 25. / 24.)), 20).iter().all(|sun| *sun == STARTING_SUN));
+        
+        let (enum_variants, fuse_map) = setup();
+        let unaffordable: u32 = (0..10)
+            .map(|seed| RandomisationData::restrictions(seed, &GenerationOptions::default(), &enum_variants, &fuse_map).unwrap().stats.unaffordable)
+            .sum();
+        assert!(unaffordable > 0);
+    }
+    
+    #[test]
+    fn rerolls() {
+        let (enum_variants, fuse_map) = setup();
+        
+        let with_rerolls = |rerolls: Vec<(u8, u8)>| RandomisationData::restrictions(
+            3,
+            &GenerationOptions {rerolls, zombie_stats: true, plant_damage: true, sun_production: true, ..Default::default()},
+            &enum_variants,
+            &fuse_map,
+        );
+        let base = with_rerolls(Vec::new()).unwrap();
+        let slot = 12usize;
+        let rerolled = with_rerolls(vec![(slot as u8, 1)]).unwrap();
+        assert_eq!(rerolled.level_order, base.level_order);
+        assert_eq!(rerolled.plant_order, base.plant_order);
+        
+        let level_stats = |rand_data: &RandomisationData, slot: usize| [
+            &rand_data.weights, &rand_data.spawns, &rand_data.firerates, &rand_data.cooldowns, &rand_data.costs, &rand_data.zombie_stats, &rand_data.plant_damage, &rand_data.sun_production,
+        ].map(|stat| stat.as_ref().unwrap()[slot - 1].clone());
+        for other in (1..=45).filter(|other| *other != slot) {
+            assert!(level_stats(&rerolled, other) == level_stats(&base, other), "rerolling slot {slot} changed slot {other}");
+        }
+        assert!(level_stats(&rerolled, slot) != level_stats(&base, slot));
+        for (stat, (rerolled, base)) in level_stats(&rerolled, slot).iter().zip(level_stats(&base, slot)).enumerate().skip(5) {
+            assert!(*rerolled != base, "rerolling slot {slot} kept stat table {stat}");
+        }
+        assert!(base.rerolls.is_empty());
+        assert!(matches!(rerolled.rerolls[..], [(rerolled_slot, 1..)] if rerolled_slot as usize == slot));
+        assert!(level_stats(&with_rerolls(rerolled.rerolls.clone()).unwrap(), slot) == level_stats(&rerolled, slot));
+        assert!(level_stats(&with_rerolls(vec![(slot as u8, 1)]).unwrap(), slot) == level_stats(&rerolled, slot));
+        assert!(level_stats(&with_rerolls(vec![(slot as u8, 2)]).unwrap(), slot) != level_stats(&rerolled, slot));
+        
+        for bad_slot in [0, 1, 46] {
+            assert!(matches!(with_rerolls(vec![(bad_slot, 1)]), Err(GenerationError::InvalidReroll(invalid)) if invalid == bad_slot));
+        }
+    }
+    
+    #[test]
+    fn planned_rerolls() {
+        let (enum_variants, fuse_map) = setup();
+        
+        let plan = Plan::parse("(
+            version: 1,
+            slots: [(slot: 8, level: Some(3))],
+            levels: [(level: 3, plants: [(plant: Peashooter, cooldown: Some(0.5), cost: Some(2.0))])],
+        )").unwrap();
+        let with_rerolls = |rerolls: Vec<(u8, u8)>| RandomisationData::restrictions(
+            5,
+            &GenerationOptions {rerolls, plan: Some(plan.clone()), ..Default::default()},
+            &enum_variants,
+            &fuse_map,
+        ).unwrap();
+        let base = with_rerolls(Vec::new());
+        let rerolled = with_rerolls(vec![(8, 1)]);
+        assert_eq!(rerolled.level_order[7], 3);
+        for rand_data in [&base, &rerolled] {
+            assert_eq!(rand_data.cooldowns.as_ref().unwrap()[7][Unlockable::Peashooter as usize], 0);
+            assert_eq!(rand_data.costs.as_ref().unwrap()[7][Unlockable::Peashooter as usize], 0xFF);
+        }
+        assert!(rerolled.weights.as_ref().unwrap()[7] != base.weights.as_ref().unwrap()[7]); //the zombies weren't planned, so they still change
+    }
+    
+    #[test]
+    fn plans() {
+        let (enum_variants, fuse_map) = setup();
+        
+        let plan = Plan::parse("(
+            version: 1,
+            slots: [
+                (slot: 3, level: Some(10), unlock: Some(Squash)),
+                (slot: 6, unlock: Some(Pumpkin)),
+                (slot: 8, level: Some(3)),
+            ],
+            levels: [
+                (level: 10, zombies: Some([(NormalZombie, 4000), (ConeZombie, 1500)])),
+                (level: 3, plants: [(plant: Peashooter, cooldown: Some(0.5), cost: Some(0.5))]),
+            ],
+        )").unwrap();
+        let options = GenerationOptions {plan: Some(plan.clone()), ..Default::default()};
+        let zombie_idx = |zombie: ZombieType| ZOMBIE_DATA.get().unwrap().iter().position(|data| data.zombie_type == zombie).unwrap();
+        for seed in 0..10 {
+            let rand_data = RandomisationData::restrictions(seed, &options, &enum_variants, &fuse_map).unwrap();
+            assert_eq!((rand_data.level_order[2], rand_data.level_order[7]), (10, 3));
+            assert_eq!((rand_data.plant_order[Unlockable::Squash as usize], rand_data.plant_order[Unlockable::Pumpkin as usize]), (4, 7));
+            
+            let weights = &rand_data.weights.as_ref().unwrap()[2];
+            let weight = |idx: usize| u32::from_le_bytes(weights[idx * 4..idx * 4 + 4].try_into().unwrap());
+            assert_eq!((weight(zombie_idx(ZombieType::NormalZombie)), weight(zombie_idx(ZombieType::ConeZombie))), (4000, 1500));
+            assert_eq!((0..weights.len() / 4).filter(|idx| weight(*idx) != 0).count(), 2);
+            assert_eq!(rand_data.cooldowns.as_ref().unwrap()[7][Unlockable::Peashooter as usize], 0);
+            assert_eq!(rand_data.costs.as_ref().unwrap()[7][Unlockable::Peashooter as usize], 0);
+        }
+        
+        assert!(Plan::parse("(version: 1, slots: [(slot: 2, level: Some(5)), (slot: 2, level: Some(6))])").is_err());
+        assert!(Plan::parse("(version: 1, slots: [(slot: 2, level: Some(1))])").is_err());
+        assert!(Plan::parse("(version: 1, levels: [(level: 4, plants: [(plant: Squash, cost: Some(3.0))])])").is_err());
+        let upgrade_first = GenerationOptions {
+            plan: Some(Plan::parse("(version: 1, slots: [(slot: 5, unlock: Some(GloomShroom))])").unwrap()),
+            ..Default::default()
+        };
+        assert!(matches!(RandomisationData::restrictions(0, &upgrade_first, &enum_variants, &fuse_map), Err(GenerationError::InvalidPlan(_))));
+    }
+}
@@ -1,7 +1,10 @@
 #![cfg_attr(target_os = "linux", feature(unix_socket_ancillary_data))]
 use std::{collections::HashMap, env, path::PathBuf, sync::{mpsc::{self, Receiver, Sender}, Arc}, thread::{self, sleep, JoinHandle}, time::Duration};
 
-use data::{init_defaults_from_dump, LevelType, LEVEL_DATA, ZOMBIE_DATA};
+use config::Config;
+use difficulty::Difficulty;
+use plant_options::PlantOptions;
+use data::{init_defaults, level_label_in_scene, LevelType, Unlockable, ZOMBIE_DATA};
 use eframe::egui::{self, Align, Context, RichText, Slider};
 use egui_file_dialog::FileDialog;
 use egui_plot::{Legend, Line, Plot};
@@ -9,10 +12,14 @@ use fxhash::FxHashMap;
 use il2cppdump::IL2CppDumper;
 use logic::RandomisationData;
 use patcher::Patch;
+use plan::Plan;
 use process::FusionProcess;
+use settings::Cfg;
+use spoiler::Spoiler;
 use rand::{RngCore, SeedableRng};
 use rand_chacha::ChaCha8Rng;
-use util::{hash_str, CommonError};
+use rules::init_rules;
+use util::CommonError;
 
 pub mod il2cppdump;
 pub mod patcher;
@@ -20,6 +27,14 @@ pub mod process;
 pub mod util;
 pub mod data;
 pub mod logic;
+pub mod headless;
+pub mod spoiler;
+pub mod settings;
+pub mod config;
+pub mod rules;
+pub mod difficulty;
+pub mod plant_options;
+pub mod plan;
 
 enum AppState {
     Disconnected,
@@ -30,26 +45,22 @@ enum AppState {
 enum AsmEvent {
     Init,
     LevelInfo(LevelUiData),
+    GenerationFailed(String),
+    Rerolls(Vec<(u8, u8)>), //the reroll counts generation ended up using
 }
 
 enum AppEvent {
     Conf(Cfg),
     Die,
     Dump(PathBuf),
+    Spoiler(PathBuf),
     Ping,
 }
 
-#[derive(Clone)]
-struct Cfg {
-    firerates_enabled: bool,
-    costs_enabled:     bool,
-    cooldowns_enabled: bool,
-    spawns_enabled:    bool,
-    tweaks_enabled:    bool,
-    restrictions:      bool,
-    sounds:            bool,
-    seed:            String,
-    sound_chance:       f32,
+enum DialogPurpose {
+    Dump,
+    Spoiler,
+    Plan,
 }
 
 struct FusionData {
@@ -63,39 +74,52 @@ struct LevelUiData {
     level: usize,
     zombies: Vec<u32>,
     wave_data: Vec<f32>,
+    flags: u8,
+    scene: LevelType,
+    weight_scaling: Option<(f32, f32)>,
 }
 
 struct App {
     state:         AppState,
     file_dialog:   FileDialog,
+    dialog_purpose: DialogPurpose,
     fusion_data:   Option<FusionData>,
     level_ui_data: Option<LevelUiData>,
     submitted:     bool,
     cfg:           Cfg,
+    settings_input: String,
+    settings_error: Option<String>,
+    generation_error: Option<String>,
+    config:        Config,
 }
 
 impl App {
     fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        let mut seed_rng = ChaCha8Rng::from_os_rng();
-        let seed = seed_rng.next_u64().to_string();
+        let config = Config::load();
+        let cfg = config.cfg.clone().unwrap_or_else(|| {
+            let mut seed_rng = ChaCha8Rng::from_os_rng();
+            Cfg {
+                seed: seed_rng.next_u64().to_string(),
+                ..Default::default()
+            }
+        });
+        let mut file_dialog = FileDialog::new();
+        if let Some(dump_dir) = &config.dump_dir {
+            file_dialog = file_dialog.initial_directory(dump_dir.clone());
+        }
         
         let mut app = Self {
             state: AppState::Disconnected,
-            file_dialog: FileDialog::new(),
+            file_dialog,
+            dialog_purpose: DialogPurpose::Dump,
             fusion_data: None,
             submitted:  false,
             level_ui_data: None,
-            cfg: Cfg {
-                firerates_enabled: true,
-                costs_enabled:     true,
-                cooldowns_enabled: true,
-                spawns_enabled:    true,
-                tweaks_enabled:    true,
-                restrictions:      true,
-                sounds:           false,
-                seed,
-                sound_chance: 0.2,
-            },
+            cfg,
+            settings_input: String::new(),
+            settings_error: None,
+            generation_error: None,
+            config,
         };
         
         app.attempt_to_find_fusion(&cc.egui_ctx);
@@ -124,9 +148,16 @@ impl App {
         self.state = AppState::OptionsMenu;
     }
     
+    fn save_config(&mut self) {
+        self.config.cfg = Some(self.cfg.clone());
+        if let Err(err) = self.config.save() {
+            println!("Failed to save config: {err}");
+        }
+    }
+    
     fn try_send_to_poll_thread(&mut self, event: AppEvent) {
         if let Some(fusion_data) = self.fusion_data.as_mut() {
-            if let Err(_) = fusion_data.atx.send(event) {
+            if fusion_data.atx.send(event).is_err() {
                 self.state = AppState::Disconnected;
                 self.submitted = false;
                 self.fusion_data = None;
@@ -145,6 +176,11 @@ impl App {
         let spawns_patch    = Patch::new(include_bytes!(concat!(env!("OUT_DIR"), "/spawns.o"))).unwrap();
         let tweaks_patch    = Patch::new(include_bytes!(concat!(env!("OUT_DIR"), "/tweaks.o"))).unwrap();
         let sounds_patch    = Patch::new(include_bytes!(concat!(env!("OUT_DIR"), "/sounds.o"))).unwrap();
+        let conveyor_patch  = Patch::new(include_bytes!(concat!(env!("OUT_DIR"), "/conveyor.o"))).unwrap();
+        let flags_patch     = Patch::new(include_bytes!(concat!(env!("OUT_DIR"), "/flags.o"))).unwrap();
+        let scenes_patch    = Patch::new(include_bytes!(concat!(env!("OUT_DIR"), "/scenes.o"))).unwrap();
+        let zombie_stats_patch = Patch::new(include_bytes!(concat!(env!("OUT_DIR"), "/zombie_stats.o"))).unwrap();
+        let plant_stats_patch  = Patch::new(include_bytes!(concat!(env!("OUT_DIR"), "/plant_stats.o"))).unwrap();
         
         let mut cfg = Cfg {
             firerates_enabled: false,
@@ -154,8 +190,20 @@ impl App {
             tweaks_enabled:    false,
             restrictions:      false,
             sounds:            false,
+            conveyors_enabled: false,
+            flag_counts_enabled: false,
+            scenes_enabled: false,
+            zombie_stats_enabled: false,
+            damage_enabled: false,
+            sun_enabled: false,
+            fusions_enabled: false,
+            progressive_weights_enabled: false,
             seed:     "".to_string(),
             sound_chance:        0.0,
+            difficulty: Difficulty::Standard,
+            plant_options: PlantOptions::default(),
+            rerolls: Vec::new(),
+            plan: None,
         };
         
         for event in prx.iter() {
@@ -185,15 +233,17 @@ impl App {
                     
                     dumper = Arc::into_inner(dump_arc).unwrap();
                 }
+                AppEvent::Spoiler(_) |
                 AppEvent::Ping => {
                     
                 }
             }
         }
         
-        init_defaults_from_dump(&dumper);
+        let enum_variants = dumper.enum_variants();
+        init_defaults(&enum_variants);
         
-        let sym_tab: FxHashMap<String, u64> = Patch::apply_patches(&[
+        let mut patches = vec![
             base_patch,
             tutorials_patch,
             firerates_patch,
@@ -202,7 +252,25 @@ impl App {
             spawns_patch,
             tweaks_patch,
             sounds_patch,
-        ], &dumper, &mut fusion);
+        ];
+        if cfg.conveyors_enabled {
+            patches.push(conveyor_patch); //replaces the conveyor's own pick, so it is left out unless needed
+        }
+        if cfg.flag_counts_enabled {
+            patches.push(flags_patch);
+        }
+        if cfg.scenes_enabled {
+            patches.push(scenes_patch);
+        }
+        if cfg.zombie_stats_enabled {
+            patches.push(zombie_stats_patch);
+        }
+        if cfg.damage_enabled || cfg.sun_enabled {
+            patches.push(plant_stats_patch); //unused tables stay at 1x
+        }
+        let sym_tab: FxHashMap<String, u64> = Patch::apply_patches(&patches, &dumper, &mut fusion);
+        let (_, plant_ids, _) = RandomisationData::get_plant_map_and_ids(&enum_variants);
+        fusion.write_memory(*sym_tab.get("max_plant_type").unwrap(), &plant_ids.last().unwrap().to_le_bytes()).unwrap();
         
         println!("Game patched!");
         
@@ -213,45 +281,80 @@ impl App {
         let mix_ptr_addr = *sym_tab.get("mix_data_ptr").unwrap();
         //let game_ptr_addr = *sym_tab.get("game_app_ptr").unwrap();
         let mut initialized  = false;
+        let mut generation_failed = false;
         let mut mem_read_vec = Vec::new();
         let mut fuse_map: FxHashMap<u32,[u32;2]> = HashMap::default();
+        let mut mix_ptrs: FxHashMap<u32, u64> = HashMap::default();
         let mut rand_data: Option<RandomisationData> = None;
         loop {
             sleep(Duration::from_millis(10));
-            loop {
-                if let Ok(msg) = prx.try_recv() {
-                    match msg {
-                        AppEvent::Conf(_new_cfg) => {
-                            panic!("Config recieved at wrong time!")
-                        },
-                        AppEvent::Die => return,
-                        AppEvent::Dump(path) => {
-                            match dumper.output_functions(path.clone().join("functions.txt")) {
-                                Ok(())  => println!("Successfully output functions"),
-                                Err(err) => println!("Failed to output functions: {err}"),
+            while let Ok(msg) = prx.try_recv() {
+                match msg {
+                    AppEvent::Conf(new_cfg) => {
+                        if initialized { //a reroll, which keeps the level and plant order so only the level data changes
+                            match new_cfg.randomise(&enum_variants, &fuse_map) {
+                                Ok(new_rand_data) => {
+                                    if new_rand_data.rerolls != new_cfg.rerolls {
+                                        ptx.send(AsmEvent::Rerolls(new_rand_data.rerolls.clone())).unwrap();
+                                        ctxt.request_repaint();
+                                    }
+                                    rand_data = Some(new_rand_data);
+                                    cfg = new_cfg;
+                                }
+                                Err(err) => println!("Failed to reroll seed {}: {err}", new_cfg.seed),
                             }
-                            
-                            let dump_arc = Arc::new(dumper);
-                            
-                            match dump_arc.output_disasm(path.clone().join("disasm.s")) {
-                                Ok(())  => println!("Successfully output disasm"),
-                                Err(err) => println!("Failed to output disasm: {err}"),
+                            continue;
+                        }
+                        if !generation_failed {
+                            panic!("Config recieved at wrong time!")
+                        }
+                        cfg = new_cfg;
+                        generation_failed = false;
+                    },
+                    AppEvent::Die => return,
+                    AppEvent::Dump(path) => {
+                        match dumper.output_functions(path.clone().join("functions.txt")) {
+                            Ok(())  => println!("Successfully output functions"),
+                            Err(err) => println!("Failed to output functions: {err}"),
+                        }
+                        
+                        let dump_arc = Arc::new(dumper);
+                        
+                        match dump_arc.output_disasm(path.clone().join("disasm.s")) {
+                            Ok(())  => println!("Successfully output disasm"),
+                            Err(err) => println!("Failed to output disasm: {err}"),
+                        }
+                        
+                        match dump_arc.output_structs(path.clone().join("structs.rs")) {
+                            Ok(())  => println!("Successfully output structs"),
+                            Err(err) => println!("Failed to output structs: {err}"),
+                        }
+                        
+                        dumper = Arc::into_inner(dump_arc).unwrap();
+                        
+                        if initialized {
+                            match headless::write_fuse_map(path.clone().join("fuse_map.txt"), &fuse_map) {
+                                Ok(())  => println!("Successfully output fuse map"),
+                                Err(err) => println!("Failed to output fuse map: {err}"),
                             }
-                            
-                            match dump_arc.output_structs(path.clone().join("structs.rs")) {
-                                Ok(())  => println!("Successfully output structs"),
-                                Err(err) => println!("Failed to output structs: {err}"),
+                        }
+                    }
+                    AppEvent::Spoiler(path) => {
+                        if let Some(rand_data) = rand_data.as_ref() {
+                            match Spoiler::new(&cfg.seed, rand_data, &enum_variants).output(path) {
+                                Ok(())  => println!("Successfully output spoiler log"),
+                                Err(err) => println!("Failed to output spoiler log: {err}"),
                             }
-                            
-                            dumper = Arc::into_inner(dump_arc).unwrap();
                         }
-                        AppEvent::Ping => {}
                     }
-                } else {
-                    break;
+                    AppEvent::Ping => {}
                 }
             }
             
+            if generation_failed {
+                continue; //the game stays paused until the seed is rerolled
+            }
+            
             if let Err(err) = fusion.read_memory(wait_addr, 1, &mut mem_read_vec) {
                 match err.downcast::<CommonError>() {
                     Err(err) => panic!("Failed to read memory: {err}"),
@@ -273,37 +376,51 @@ impl App {
                 ptx.send(AsmEvent::Init).unwrap();
                 ctxt.request_repaint();
                 
-                fusion.read_memory(mix_ptr_addr, 8, &mut mem_read_vec).unwrap();
-                let mix_data_addr = u64::from_le_bytes(mem_read_vec[0..8].try_into().unwrap());
-                
-                fusion.read_memory(mix_data_addr + 0x10, 8, &mut mem_read_vec).unwrap();
-                let mix_array_addr = u64::from_le_bytes(mem_read_vec[0..8].try_into().unwrap());
-                
-                fusion.read_memory(mix_array_addr, 0x20, &mut mem_read_vec).unwrap();
-                let mix_array_width = u32::from_le_bytes(mem_read_vec[0x18..0x1C].try_into().unwrap()) as usize;
-                
-                fusion.read_memory(mix_array_addr + 0x20, mix_array_width * 8, &mut mem_read_vec).unwrap();
-                
-                let mut tmp_read_vec = Vec::new();
-                for (i, bytes) in mem_read_vec.chunks_exact(8).enumerate() {
-                    let mix_ptr = u64::from_le_bytes(bytes.try_into().unwrap());
-                    if mix_ptr != 0 {
-                        fusion.read_memory(mix_ptr + 0x10, 8, &mut tmp_read_vec).unwrap();
-                        let plant_1 = u32::from_le_bytes(tmp_read_vec[0..4].try_into().unwrap());
-                        let plant_2 = u32::from_le_bytes(tmp_read_vec[4..8].try_into().unwrap());
-                        if plant_1 as usize != i && plant_2 as usize != i {
-                            //println!("{i}: {} + {}", plant_1, plant_2);
-                            fuse_map.insert(i as u32, [plant_1, plant_2]);
+                if fuse_map.is_empty() {
+                    fusion.read_memory(mix_ptr_addr, 8, &mut mem_read_vec).unwrap();
+                    let mix_data_addr = u64::from_le_bytes(mem_read_vec[0..8].try_into().unwrap());
+                    
+                    fusion.read_memory(mix_data_addr + 0x10, 8, &mut mem_read_vec).unwrap();
+                    let mix_array_addr = u64::from_le_bytes(mem_read_vec[0..8].try_into().unwrap());
+                    
+                    fusion.read_memory(mix_array_addr, 0x20, &mut mem_read_vec).unwrap();
+                    let mix_array_width = u32::from_le_bytes(mem_read_vec[0x18..0x1C].try_into().unwrap()) as usize;
+                    
+                    fusion.read_memory(mix_array_addr + 0x20, mix_array_width * 8, &mut mem_read_vec).unwrap();
+                    
+                    let mut tmp_read_vec = Vec::new();
+                    for (i, bytes) in mem_read_vec.chunks_exact(8).enumerate() {
+                        let mix_ptr = u64::from_le_bytes(bytes.try_into().unwrap());
+                        if mix_ptr != 0 {
+                            fusion.read_memory(mix_ptr + 0x10, 8, &mut tmp_read_vec).unwrap();
+                            let plant_1 = u32::from_le_bytes(tmp_read_vec[0..4].try_into().unwrap());
+                            let plant_2 = u32::from_le_bytes(tmp_read_vec[4..8].try_into().unwrap());
+                            if plant_1 as usize != i && plant_2 as usize != i {
+                                //println!("{i}: {} + {}", plant_1, plant_2);
+                                fuse_map.insert(i as u32, [plant_1, plant_2]);
+                                mix_ptrs.insert(i as u32, mix_ptr);
+                            }
                         }
                     }
                 }
                 
-                rand_data = Some(if cfg.restrictions {
-                    RandomisationData::restrictions(hash_str(&cfg.seed), &dumper, &fuse_map)
-                } else {
-                    RandomisationData::no_restrictions(hash_str(&cfg.seed), &dumper, &fuse_map)
-                });
+                match cfg.randomise(&enum_variants, &fuse_map) {
+                    Ok(new_rand_data) => rand_data = Some(new_rand_data),
+                    Err(err) => {
+                        println!("Failed to generate seed {}: {err}", cfg.seed);
+                        ptx.send(AsmEvent::GenerationFailed(err.to_string())).unwrap();
+                        ctxt.request_repaint();
+                        generation_failed = true;
+                        continue;
+                    }
+                }
                 
+                let stats = &rand_data.as_ref().unwrap().stats;
+                if cfg.restrictions {
+                    println!("Chosen plant solutions: {:?}", stats.chosen_solutions);
+                    println!("Blacklist: {:?}", stats.blacklist);
+                    println!("Plant order: {:?}", stats.unlock_order);
+                }
                 println!("Level order: {:?}", rand_data.as_ref().unwrap().level_order);
                 
                 fusion.write_memory(
@@ -315,6 +432,12 @@ impl App {
                     *sym_tab.get("plant_lut").unwrap(),
                     &unsafe { rand_data.as_ref().unwrap_unchecked() }.plant_order,
                 ).unwrap();
+                
+                let recipes = unsafe { rand_data.as_ref().unwrap_unchecked() }.fusions.as_ref().unwrap_or(&fuse_map); //also undoes the recipes of an earlier attempt
+                for (fusion_id, mix_ptr) in &mix_ptrs {
+                    let [plant_1, plant_2] = recipes[fusion_id];
+                    fusion.write_memory(mix_ptr + 0x10, &[plant_1.to_le_bytes(), plant_2.to_le_bytes()].concat()).unwrap();
+                }
                 initialized = true;
             }
             
@@ -326,6 +449,20 @@ impl App {
                 if let Some(costs) = &rand_data.costs {
                     fusion.write_memory(*sym_tab.get("plant_cost_table").unwrap(), &costs[level_idx as usize]).unwrap();
                 }
+                if let Some(conveyors) = &rand_data.conveyors {
+                    let mut weights = [0u8; 48]; //all zero makes the conveyor pick the way it normally would
+                    weights[..conveyors[level_idx as usize].len()].copy_from_slice(&conveyors[level_idx as usize]);
+                    fusion.write_memory(*sym_tab.get("conveyor_weights").unwrap(), &weights).unwrap();
+                }
+                if let Some(flag_counts) = &rand_data.flag_counts {
+                    fusion.write_memory(*sym_tab.get("flag_count").unwrap(), &flag_counts[level_idx as usize]).unwrap();
+                }
+                if let Some(scenes) = &rand_data.scenes {
+                    fusion.write_memory(*sym_tab.get("scene_type").unwrap(), &(scenes[level_idx as usize][0] as u32).to_le_bytes()).unwrap(); //scenes.s maps it to the SceneType
+                }
+                if let Some(zombie_stats) = &rand_data.zombie_stats {
+                    fusion.write_memory(*sym_tab.get("zombie_stat_table").unwrap(), &zombie_stats[level_idx as usize]).unwrap();
+                }
                 let spawn_vec = if cfg.spawns_enabled {
                     if let Some(spawns) = &rand_data.spawns {
                         fusion.write_memory(*sym_tab.get("zombie_spawn_bitfield").unwrap(), &spawns[level_idx as usize]).unwrap();
@@ -356,23 +493,37 @@ impl App {
                 if let Some(firerates) = &rand_data.firerates {
                     fusion.write_memory(*sym_tab.get("plant_firerate_table").unwrap(), &firerates[level_idx as usize]).unwrap();
                 }
+                if let Some(plant_damage) = &rand_data.plant_damage {
+                    fusion.write_memory(*sym_tab.get("plant_damage_table").unwrap(), &plant_damage[level_idx as usize]).unwrap();
+                }
+                if let Some(sun_production) = &rand_data.sun_production {
+                    fusion.write_memory(*sym_tab.get("plant_sun_table").unwrap(), &sun_production[level_idx as usize]).unwrap();
+                }
                 if let Some(sound_seeds) = &rand_data.sound_seeds {
                     fusion.write_memory(*sym_tab.get("sound_rng_seed").unwrap(), &sound_seeds[level_idx as usize].to_le_bytes()).unwrap();
                     fusion.write_memory(*sym_tab.get("sound_chance").unwrap(), &((if cfg.sounds {cfg.sound_chance} else {0.0} * 4294967296.) as u64).to_le_bytes()).unwrap();
                 }
                 
                 let zombie_data = ZOMBIE_DATA.get().unwrap();
-                let freq_data = rand_data.compute_zombie_freq_data_cached(&spawn_vec, rand_data.level_order[level_idx as usize] as usize).unwrap();
-                let mut zombies: Vec<u32> = spawn_vec.into_iter().map(|(id, _)| id).collect();
-                let wave_data = freq_data.raw_averages;
-                zombies.sort_by_key(|idx| zombie_data[*idx as usize].default_points);
-                
-                ptx.send(AsmEvent::LevelInfo(LevelUiData {
-                    level_idx: level_idx as usize,
-                    level: rand_data.level_order[level_idx as usize] as usize,
-                    zombies,
-                    wave_data,
-                })).unwrap();
+                let level = rand_data.level_order[level_idx as usize] as usize;
+                match rand_data.compute_zombie_freq_data_cached(&spawn_vec, level).and_then(|freq_data| Ok((freq_data, rand_data.flag_count(level)?))) {
+                    Ok((freq_data, flags)) => {
+                        let mut zombies: Vec<u32> = spawn_vec.into_iter().map(|(id, _)| id).collect();
+                        let wave_data = freq_data.raw_averages;
+                        zombies.sort_by_key(|idx| zombie_data[*idx as usize].default_points);
+                        
+                        ptx.send(AsmEvent::LevelInfo(LevelUiData {
+                            level_idx: level_idx as usize,
+                            level,
+                            zombies,
+                            wave_data,
+                            flags,
+                            scene: rand_data.scene(level),
+                            weight_scaling: rand_data.weight_scaling.as_ref().map(|scaling| scaling[level_idx as usize]),
+                        })).unwrap();
+                    }
+                    Err(err) => println!("Failed to compute wave composition: {err}"),
+                }
             }
             
             fusion.write_memory(wait_addr, &[0]).unwrap();
@@ -391,13 +542,15 @@ impl App {
             .collect();
         let zombie_data = ZOMBIE_DATA.get().unwrap();
         Line::new(points)
-            .name(zombie_data[idx as usize].name)
+            .name(zombie_data[idx].name)
             .width(5.)
     }
 }
 
 impl Drop for App {
     fn drop(&mut self) {
+        self.save_config();
+        
         if let Some(data) = self.fusion_data.take() {
             data.atx.send(AppEvent::Die).unwrap();
             
@@ -409,36 +562,40 @@ impl Drop for App {
 impl eframe::App for App {
     fn update(&mut self, ctxt: &egui::Context, _frame: &mut eframe::Frame) {
         self.try_send_to_poll_thread(AppEvent::Ping);
+        let mut rerolls_changed = false;
         if let Some(data) = self.fusion_data.as_mut() {
-            loop {
-                if let Ok(msg) = data.arx.try_recv() {
-                    match msg {
-                        AsmEvent::Init => {
-                            self.state = AppState::InGame;
-                        }
-                        AsmEvent::LevelInfo(info) => {
-                            self.level_ui_data = Some(info);
-                        }
+            while let Ok(msg) = data.arx.try_recv() {
+                match msg {
+                    AsmEvent::Init => {
+                        self.state = AppState::InGame;
+                    }
+                    AsmEvent::LevelInfo(info) => {
+                        self.level_ui_data = Some(info);
+                    }
+                    AsmEvent::GenerationFailed(err) => {
+                        self.generation_error = Some(err);
+                    }
+                    AsmEvent::Rerolls(rerolls) => { //an impossible reroll was rerolled again, so the settings code records that
+                        self.cfg.rerolls = rerolls;
+                        self.config.add_recent_seed(&self.cfg);
+                        rerolls_changed = true;
                     }
-                } else {
-                    break;
                 }
             }
         }
+        if rerolls_changed {
+            self.save_config();
+        }
         
         if let AppState::Disconnected = self.state {
             egui::TopBottomPanel::bottom("Disconnected").show(ctxt, |ui| {
                 ui.horizontal(|ui| {
                     ui.label(
-                        RichText::new(format!(
-                            "Failed to find running instance of PVZ Fusion",
-                        )).size(24.)
+                        RichText::new("Failed to find running instance of PVZ Fusion").size(24.)
                     );
                     ui.with_layout(egui::Layout::right_to_left(Align::Max), |ui| {
                         if ui.button(
-                            RichText::new(format!(
-                                "Retry",
-                            )).size(24.)
+                            RichText::new("Retry").size(24.)
                         ).clicked() {
                             self.attempt_to_find_fusion(ctxt);
                         }
@@ -462,17 +619,68 @@ impl eframe::App for App {
                         }
                         if let AppState::OptionsMenu = self.state {
                             if ui.button("Dump").clicked() {
+                                self.dialog_purpose = DialogPurpose::Dump;
                                 self.file_dialog.pick_directory();
                             }
                             if !self.submitted && ui.button("Submit").clicked() {
                                 self.submitted = true;
+                                self.config.add_recent_seed(&self.cfg);
+                                self.save_config();
                                 self.try_send_to_poll_thread(AppEvent::Conf(self.cfg.clone()));
                             }
                         }
                     });
+                    if !self.config.recent_seeds.is_empty() {
+                        ui.horizontal(|ui| {
+                            if self.submitted {ui.disable();}
+                            ui.label("Recent seeds: ");
+                            let mut picked = None;
+                            egui::ComboBox::from_id_salt("Recent seeds")
+                                .selected_text(&self.cfg.seed)
+                                .width(400.)
+                                .show_ui(ui, |ui| {
+                                    for recent in &self.config.recent_seeds {
+                                        if ui.selectable_label(recent.cfg.seed == self.cfg.seed, recent.label()).clicked() {
+                                            picked = Some(recent.cfg.clone());
+                                        }
+                                    }
+                                });
+                            if let Some(cfg) = picked {
+                                self.cfg = cfg;
+                            }
+                        });
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Settings code: ");
+                        let settings_code = self.cfg.to_settings_string();
+                        let hover_text = match &settings_code {
+                            Ok(code) => code.clone(),
+                            Err(err) => err.to_string(),
+                        };
+                        if ui.button("Copy").on_hover_text(hover_text).clicked() {
+                            match settings_code {
+                                Ok(code) => ctxt.copy_text(code),
+                                Err(err) => self.settings_error = Some(err.to_string()),
+                            }
+                        }
+                        if self.submitted {ui.disable();}
+                        ui.text_edit_singleline(&mut self.settings_input).on_hover_text("Paste a settings code here to use the same seed and options as someone else");
+                        if ui.button("Load").clicked() {
+                            match Cfg::from_settings_string(&self.settings_input) {
+                                Ok(cfg) => {
+                                    self.cfg = cfg;
+                                    self.settings_error = None;
+                                }
+                                Err(err) => self.settings_error = Some(err.to_string()),
+                            }
+                        }
+                    });
+                    if let Some(err) = &self.settings_error {
+                        ui.colored_label(egui::Color32::RED, err);
+                    }
                     ui.horizontal_wrapped(|ui| {
                         if self.submitted {ui.disable();}
-                        let layout = ui.layout().clone().with_main_justify(true).with_main_align(Align::Min);
+                        let layout = (*ui.layout()).with_main_justify(true).with_main_align(Align::Min);
                         let size: egui::Vec2 = [135.0, 20.0].into();
                         ui.allocate_ui_with_layout(size, layout, |ui| {
                             ui.checkbox(&mut self.cfg.firerates_enabled, "Random firerates").on_hover_ui(|ui| {
@@ -513,10 +721,67 @@ The range of randomisation is between 0.1x weight and 10x weight, with heavy bia
                             });
                             
                         });
+                        ui.allocate_ui_with_layout(size, layout, |ui| {
+                            ui.checkbox(&mut self.cfg.conveyors_enabled, "Random conveyors").on_hover_ui(|ui| {
+                                ui.label("Random conveyors randomises which plants come on the conveyor belt in conveyor levels and how often each one comes.
+With restrictions, each conveyor level still gets the plants needed for its water or roof, and for its zombies where possible.");
+                            });
+                            
+                        });
+                        ui.allocate_ui_with_layout(size, layout, |ui| {
+                            ui.checkbox(&mut self.cfg.flag_counts_enabled, "Random flags").on_hover_ui(|ui| {
+                                ui.label("Random flags gives each level one flag more or less than normal, between 1 and 4 flags.
+With restrictions, levels with more flags need more firepower before they can be picked.");
+                            });
+                            
+                        });
+                        ui.allocate_ui_with_layout(size, layout, |ui| {
+                            ui.checkbox(&mut self.cfg.scenes_enabled, "Random scenes").on_hover_ui(|ui| {
+                                ui.label("Random scenes gives each level a random day, night, pool, fog or roof scene, keeping its zombies.
+Water zombies are left out of levels without water, and conveyor levels keep their own scene.
+With restrictions, levels still need a water, pot or night solution for their new scene.");
+                            });
+                            
+                        });
+                        ui.allocate_ui_with_layout(size, layout, |ui| {
+                            ui.checkbox(&mut self.cfg.zombie_stats_enabled, "Random zombie stats").on_hover_ui(|ui| {
+                                ui.label("Random zombie stats randomises the health, walking speed and eating damage of each zombie in each level, with the same range as plant stats.
+With restrictions, zombies with at least 1.5x health need the same answers as tougher zombies.");
+                            });
+                            
+                        });
+                        ui.allocate_ui_with_layout(size, layout, |ui| {
+                            ui.checkbox(&mut self.cfg.damage_enabled, "Random damage").on_hover_ui(|ui| {
+                                ui.label("Random damage randomises how much damage each plant does in each level, with the same range as firerates.
+Fusions get the average of the plants they are made from, and the peashooter never does less than normal.");
+                            });
+                            
+                        });
+                        ui.allocate_ui_with_layout(size, layout, |ui| {
+                            ui.checkbox(&mut self.cfg.sun_enabled, "Random sun").on_hover_ui(|ui| {
+                                ui.label("Random sun randomises how much sun each sun producing plant makes in each level, with the same range as firerates.
+Fusions get the average of the plants they are made from, and the sunflower never makes less than normal.");
+                            });
+                            
+                        });
+                        ui.allocate_ui_with_layout(size, layout, |ui| {
+                            ui.checkbox(&mut self.cfg.fusions_enabled, "Random fusions").on_hover_ui(|ui| {
+                                ui.label("Random fusions shuffles which two plants fuse into each fusion.
+Only fusions made from two base plants are shuffled, and random firerates, damage and sun follow the new recipes.");
+                            });
+                            
+                        });
+                        ui.allocate_ui_with_layout(size, layout, |ui| {
+                            ui.checkbox(&mut self.cfg.progressive_weights_enabled, "Progressive weights").on_hover_ui(|ui| {
+                                ui.label("Progressive weights scales zombie weights by how far into the run a level is, no matter which level it is.
+Zombies other than the basic zombie go from 0.5x in the second slot to 1.5x in the last, and elites go from 0.25x to 2.25x.");
+                            });
+                            
+                        });
                     });
                     ui.horizontal_wrapped(|ui| {
                         if self.submitted {ui.disable();}
-                        let layout = ui.layout().clone().with_main_justify(true).with_main_align(Align::Min);
+                        let layout = (*ui.layout()).with_main_justify(true).with_main_align(Align::Min);
                         let size: egui::Vec2 = [135.0, 20.0].into();
                         let size_x2: egui::Vec2 = [270.0, 20.0].into();
                         ui.allocate_ui_with_layout(size, layout, |ui| {
@@ -546,30 +811,108 @@ More will likely be added in the future.");
                             ui.label("Sound chance");
                         });
                     });
+                    ui.horizontal(|ui| {
+                        if self.submitted {ui.disable();}
+                        ui.label("Difficulty: ");
+                        egui::ComboBox::from_id_salt("Difficulty")
+                            .selected_text(format!("{:?}", self.cfg.difficulty))
+                            .show_ui(ui, |ui| {
+                                for difficulty in Difficulty::ALL {
+                                    ui.selectable_value(&mut self.cfg.difficulty, difficulty, format!("{difficulty:?}")).on_hover_text(difficulty.description());
+                                }
+                            }).response.on_hover_text(self.cfg.difficulty.description());
+                    });
+                    ui.horizontal_wrapped(|ui| {
+                        if self.submitted || !self.cfg.restrictions {ui.disable();}
+                        let options = &mut self.cfg.plant_options;
+                        let plants: Vec<Unlockable> = (Unlockable::CherryBomb as u8..=Unlockable::CobCannon as u8).filter_map(Unlockable::from_u8).collect();
+                        ui.label("First plant: ");
+                        egui::ComboBox::from_id_salt("First plant")
+                            .selected_text(options.first_plant.map_or("Random".to_string(), |plant| format!("{plant:?}")))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut options.first_plant, None, "Random");
+                                for plant in &plants {
+                                    ui.selectable_value(&mut options.first_plant, Some(*plant), format!("{plant:?}"));
+                                }
+                            }).response.on_hover_text("The plant unlocked by beating the first level, only used with restrictions");
+                        for (label, list) in [("Early plants", &mut options.early_plants), ("Excluded plants", &mut options.excluded)] {
+                            egui::ComboBox::from_id_salt(label)
+                                .selected_text(format!("{label} ({})", list.len()))
+                                .show_ui(ui, |ui| {
+                                    for plant in &plants {
+                                        let pos = list.iter().position(|x| x == plant);
+                                        if ui.selectable_label(pos.is_some(), format!("{plant:?}")).clicked() {
+                                            match pos {
+                                                Some(pos) => {list.remove(pos);}
+                                                None => list.push(*plant),
+                                            }
+                                        }
+                                    }
+                                }).response.on_hover_text(if label == "Early plants" {
+                                    "These plants are always unlocked by one of the first few levels, only used with restrictions"
+                                } else {
+                                    "These plants are never unlocked, only used with restrictions"
+                                });
+                        }
+                        ui.add(egui::DragValue::new(&mut options.early_levels).range(2..=45)).on_hover_text("How many levels the early plants are unlocked within");
+                        ui.label("Early levels");
+                        ui.checkbox(&mut options.weighted, "Weighted plants").on_hover_text("Unlocks more useful plants earlier and avoids too many plants with the same role in a row, only used with restrictions");
+                        if ui.button("Load plan").on_hover_text("Loads a plan file that fixes some levels, unlocks, zombies and plant stats, the rest is randomised around them, only used with restrictions").clicked() {
+                            self.dialog_purpose = DialogPurpose::Plan;
+                            self.file_dialog.pick_file();
+                        }
+                        if let Some(plan) = &self.cfg.plan {
+                            ui.label(format!("Plan with {} slots and {} levels", plan.slots.len(), plan.levels.len()));
+                            if ui.button("Clear plan").clicked() {
+                                self.cfg.plan = None;
+                            }
+                        }
+                    });
                 });
             }
             
             AppState::InGame => {
                 egui::CentralPanel::default().show(ctxt, |ui| {
                     ui.style_mut().text_styles.get_mut(&egui::TextStyle::Body).unwrap().size = 20.;
+                    if let Some(err) = self.generation_error.clone() {
+                        ui.colored_label(egui::Color32::RED, format!("Seed {} could not be generated: {err}", self.cfg.seed));
+                        if ui.button("Reroll").on_hover_text("Generates a new random seed with the same options").clicked() {
+                            let mut seed_rng = ChaCha8Rng::from_os_rng();
+                            self.cfg.seed = seed_rng.next_u64().to_string();
+                            self.cfg.rerolls.clear();
+                            self.config.add_recent_seed(&self.cfg);
+                            self.save_config();
+                            self.generation_error = None;
+                            self.try_send_to_poll_thread(AppEvent::Conf(self.cfg.clone()));
+                        }
+                    }
+                    let mut reroll_slot = None;
                     if let Some(level_ui_data) = self.level_ui_data.as_ref() {
-                        let level_data = LEVEL_DATA.get().unwrap();
-                        let level_type = level_data[level_ui_data.level - 1].level_type;
-                        let world = match level_type {
-                            LevelType::Day => 1,
-                            LevelType::Night => 2,
-                            LevelType::Pool => 3,
-                            LevelType::Fog => 4,
-                            LevelType::Roof => 5,
-                        };
-                        let mut stage = 1;
-                        for level in level_data[0 .. level_ui_data.level - 1].iter().rev() {
-                            if level.level_type != level_type {
-                                break;
-                            }
-                            stage += 1;
+                        ui.horizontal(|ui| {
+                            ui.label(RichText::new(format!(
+                                "{} / {} ({} flag{})",
+                                level_label_in_scene(level_ui_data.level, level_ui_data.scene),
+                                level_ui_data.level_idx + 1,
+                                level_ui_data.flags,
+                                if level_ui_data.flags == 1 {""} else {"s"},
+                            )).size(56.));
+                            ui.with_layout(egui::Layout::right_to_left(Align::Center), |ui| {
+                                if ui.button("Spoiler log").on_hover_text("Writes spoiler.txt and spoiler.json to the chosen directory").clicked() {
+                                    self.dialog_purpose = DialogPurpose::Spoiler;
+                                    self.file_dialog.pick_directory();
+                                }
+                                let slot = level_ui_data.level_idx as u8 + 1;
+                                ui.add_enabled_ui(self.cfg.restrictions && slot > 1, |ui| {
+                                    if ui.button("Reroll level").on_hover_text("Gives this level new zombies and plant stats the next time it's started, without changing any other level.
+The settings code keeps track of rerolls, only used with restrictions").clicked() {
+                                        reroll_slot = Some(slot);
+                                    }
+                                });
+                            });
+                        });
+                        if let Some((normal, elite)) = level_ui_data.weight_scaling {
+                            ui.label(format!("Zombie weights x{normal:.2}, elites x{elite:.2}"));
                         }
-                        ui.label(RichText::new(format!("{world}-{stage} / {}", level_ui_data.level_idx + 1)).size(56.));
                         
                         let legend = Legend::default()
                             .position(egui_plot::Corner::LeftTop);
@@ -587,19 +930,52 @@ More will likely be added in the future.");
                             }
                         });
                     }
+                    if let Some(slot) = reroll_slot {
+                        self.cfg.reroll(slot);
+                        self.config.add_recent_seed(&self.cfg);
+                        self.save_config();
+                        self.try_send_to_poll_thread(AppEvent::Conf(self.cfg.clone()));
+                    }
                 });
             }
         }
         
         self.file_dialog.update(ctxt);
         if let Some(path) = self.file_dialog.take_picked() {
-            self.try_send_to_poll_thread(AppEvent::Dump(path));
+            match self.dialog_purpose {
+                DialogPurpose::Dump    => {
+                    self.config.dump_dir = Some(path.clone());
+                    self.save_config();
+                    self.try_send_to_poll_thread(AppEvent::Dump(path));
+                }
+                DialogPurpose::Spoiler => self.try_send_to_poll_thread(AppEvent::Spoiler(path)),
+                DialogPurpose::Plan    => match Plan::load_file(&path) {
+                    Ok(plan) => {
+                        self.cfg.plan = Some(plan);
+                        self.settings_error = None;
+                    }
+                    Err(err) => self.settings_error = Some(err.to_string()),
+                },
+            }
         }
         ctxt.request_repaint_after_secs(0.5);
     }
 }
 
 fn main() {
+    let args: Vec<String> = env::args().collect();
+    if headless::wants_headless(&args) {
+        if let Err(err) = headless::run(&args) {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Err(err) = init_rules() {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+    
     let mut native_options = eframe::NativeOptions::default();
     native_options.viewport = native_options.viewport.with_min_inner_size([800., 600.]);
     eframe::run_native(
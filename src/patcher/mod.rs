@@ -1,6 +1,6 @@
 use std::{collections::HashMap, hash::BuildHasherDefault, io::Write, num::NonZeroU64, ops::Range};
 use fxhash::FxHashMap;
-use iced_x86::{Code, Decoder, DecoderOptions, Encoder, Formatter, GasFormatter, Instruction, Mnemonic, OpKind, SymbolResolver, SymbolResult};
+use iced_x86::{BlockEncoder, BlockEncoderOptions, Code, Decoder, DecoderOptions, Encoder, FlowControl, Formatter, GasFormatter, Instruction, InstructionBlock, Mnemonic, OpKind, SymbolResolver, SymbolResult};
 use object::{File, Object, ObjectSection, ObjectSymbol, Relocation, RelocationKind, RelocationTarget, Section, SectionKind, Symbol, SymbolKind};
 use smallvec::SmallVec;
 
@@ -565,6 +565,33 @@ impl Patch {
             text_section_off += current_offset as u64;
         }
         
+        //an injection at the start of a method that a patch still calls as "Method.original" gets a copy of the instructions it overwrites
+        let mut trampolines: Vec<(u64, Vec<u8>)> = Vec::new();
+        let mut displaced: FxHashMap<u64, u64> = HashMap::default();
+        for patch in &patches {
+            for injection in patch.injections.iter().filter(|injection| injection.off == 0) {
+                let original_name = injection.func_name.clone() + ".original";
+                let is_used = patches.iter().any(|patch| patch.imm_vec.iter().any(|imm| matches!(imm,
+                    Immediate::UnresolvedSymbol(name, _) | Immediate::UnresolvedSymbolRel(name, _) if *name == original_name
+                )));
+                if !is_used || il2cpp_syms.contains_key(&original_name) {
+                    continue;
+                }
+                
+                let method = &meta.methods_array[*meta.methods_table.get(&injection.func_name).expect("Invalid injection function name") as usize];
+                let instructions = method.get_entry_instructions(hook_size(&injection.instructions), fusion.dll_offset as i64 - 0x1_8000_0000, meta)
+                    .unwrap_or_else(|err| panic!("Can't make {original_name}: {err}"));
+                let start = instructions[0].ip();
+                let (displaced_size, code) = assemble_trampoline(&instructions, text_section_off)
+                    .unwrap_or_else(|err| panic!("Can't make {original_name}: {err}"));
+                
+                il2cpp_syms.insert(original_name, text_section_off);
+                displaced.insert(start, displaced_size);
+                trampolines.push((text_section_off, code));
+                text_section_off += (trampolines.last().unwrap().1.len() as u64 + 0xF) & !0xF;
+            }
+        }
+        
         let text_section_size = ((text_section_off + 0xFFF) & !0xFFF) - data_section_size - fusion.asm_offset;
         
         //fusion.allocate_memory(fusion.asm_offset, data_section_size, PAGE_READWRITE);
@@ -612,7 +639,7 @@ impl Patch {
                         encoder.encode(&instruction, text_section_off + current_offset as u64).unwrap_or_else(|err| {
                             panic!(
                                 "Phase 2 encoding error: {err}\nInstruction: {}",
-                                display_instruction(&original_instruction, &patch.imm_vec, &il2cpp_syms),
+                                display_instruction(original_instruction, &patch.imm_vec, &il2cpp_syms),
                             );
                         }) as u32
                     }
@@ -624,6 +651,9 @@ impl Patch {
         let code = encoder.take_buffer();
         
         fusion.write_memory(fusion.asm_offset + data_section_size, &code).unwrap();
+        for (addr, code) in &trampolines {
+            fusion.write_memory(*addr, code).unwrap();
+        }
         
         let mut all_data: Vec<u8> = Vec::with_capacity(text_section_size as usize);
         
@@ -826,6 +856,9 @@ impl Patch {
                 }
                 
                 let code = encoder.take_buffer();
+                if let Some(displaced_size) = displaced.get(&injection.off) {
+                    assert!(code.len() as u64 <= *displaced_size, "The injection into {} is longer than the instructions its .original runs", injection.func_name);
+                }
                 fusion.write_memory(injection.off, &code).unwrap();
             }
         }
@@ -892,6 +925,24 @@ enum DataRelocSize {
     I64,
 }
 
+fn hook_size(instructions: &[Instruction]) -> u64 { //the most bytes an injection can take once it's encoded
+    instructions.iter()
+        .map(|instruction| match instruction.flow_control() {
+            FlowControl::Next => instruction.len() as u64,
+            _                 => instruction.len().max(6) as u64, //branches can grow to rel32
+        })
+        .sum()
+}
+
+fn assemble_trampoline(instructions: &[Instruction], addr: u64) -> Result<(u64, Vec<u8>), String> { //the instructions a hook overwrites moved to addr and followed by a jump back, and how many bytes of the method they were
+    let start  = instructions.first().ok_or("no instructions to move")?.ip();
+    let resume = instructions.last().unwrap().next_ip();
+    let mut instructions = instructions.to_vec();
+    instructions.push(Instruction::with_branch(Code::Jmp_rel32_64, resume).map_err(|err| err.to_string())?);
+    let encoded = BlockEncoder::encode(64, InstructionBlock::new(&instructions, addr), BlockEncoderOptions::NONE).map_err(|err| err.to_string())?;
+    Ok((resume - start, encoded.code_buffer))
+}
+
 fn get_instruction_imm_idxs(instruction: &Instruction) -> SmallVec<[(OpKind,usize);2]> {
     let mut ret = SmallVec::new();
     for (i, op) in instruction.op_kinds().enumerate() {
@@ -1047,10 +1098,8 @@ fn fill_in_imms_phase_1(instruction: &mut Instruction, imm_vec: &[Immediate], ip
                                 instruction.set_memory_displacement64(*value);
                             }
                             Immediate::UnresolvedSymbol(_, _) |
-                            Immediate::UnresolvedSymbolRel(_, _) => {
-                                if instruction.is_ip_rel_memory_operand() {
-                                    instruction.set_memory_displacement64(dll_base);
-                                }
+                            Immediate::UnresolvedSymbolRel(_, _) if instruction.is_ip_rel_memory_operand() => {
+                                instruction.set_memory_displacement64(dll_base);
                             }
                             _ => {}
                         }
@@ -1579,7 +1628,7 @@ impl DebugSymResolver {
 }
 
 impl SymbolResolver for DebugSymResolver {
-    fn symbol(&mut self, instruction: &Instruction, operand: u32, _instruction_operand: Option<u32>, addr: u64, _addr_size: u32) -> Option<SymbolResult> {
+    fn symbol(&mut self, instruction: &Instruction, operand: u32, _instruction_operand: Option<u32>, addr: u64, _addr_size: u32) -> Option<SymbolResult<'_>> {
         let imm_vec = unsafe { &*self.imm_vec };
         let _sym_tab = unsafe { &*self.sym_tab };
         let zero_imm = Immediate::Immediate(0);
@@ -1631,6 +1680,66 @@ fn display_instruction(
     let sym_resolver = Box::new(DebugSymResolver::new(imm_vec as *const [Immediate], sym_tab as *const FxHashMap<String,u64>));
     let mut ret = String::new();
     let mut formatter = GasFormatter::with_options(Some(sym_resolver), None);
-    formatter.format(&instruction, &mut ret);
+    formatter.format(instruction, &mut ret);
     ret
 }
+
+#[cfg(test)]
+mod tests {
+    use iced_x86::{Code, Decoder, DecoderOptions, Instruction};
+    
+    use crate::il2cppdump::disasm::entry_instructions;
+    use super::{assemble_trampoline, hook_size, Patch};
+    
+    const START: u64 = 0x1_8000_1000;
+    const METHOD: [u8; 24] = [
+        0x48, 0x89, 0x5C, 0x24, 0x08, //mov [rsp+8],rbx
+        0x57,                         //push rdi
+        0x48, 0x83, 0xEC, 0x20,       //sub rsp,0x20
+        0xE8, 0xF1, 0x1F, 0x00, 0x00, //call START+0x2000
+        0x48, 0x8B, 0xD9,             //mov rbx,rax
+        0x48, 0x83, 0xC4, 0x20,       //add rsp,0x20
+        0x5F,                         //pop rdi
+        0xC3,                         //ret
+    ];
+    
+    fn decode(code: &[u8], ip: u64) -> Vec<Instruction> {
+        Decoder::with_ip(64, code, ip, DecoderOptions::NONE).into_iter().collect()
+    }
+    
+    #[test]
+    fn hook_sizes() {
+        let patch = Patch::new(include_bytes!(concat!(env!("OUT_DIR"), "/conveyor.o"))).unwrap();
+        let injection = patch.injections.iter().find(|injection| injection.off == 0).unwrap();
+        assert_eq!(hook_size(&injection.instructions), 6); //a jmp can grow to rel32
+    }
+    
+    #[test]
+    fn trampolines() {
+        let addr = START + 0x10_0000;
+        let instructions = entry_instructions(&METHOD, START, 6).unwrap();
+        assert_eq!(instructions.len(), 2);
+        let (displaced, code) = assemble_trampoline(&instructions, addr).unwrap();
+        assert_eq!(displaced, 6);
+        assert_eq!(code[..6], METHOD[..6]);
+        let moved = decode(&code, addr);
+        assert_eq!(moved.len(), 3);
+        assert_eq!((moved[2].code(), moved[2].near_branch64()), (Code::Jmp_rel32_64, START + 6));
+        
+        let instructions = entry_instructions(&METHOD, START, 11).unwrap(); //the call is cut through, so it moves too
+        let (displaced, code) = assemble_trampoline(&instructions, addr).unwrap();
+        assert_eq!(displaced, 15);
+        let moved = decode(&code, addr);
+        assert_eq!(moved.len(), 5);
+        assert_eq!((moved[3].code(), moved[3].near_branch64()), (Code::Call_rel32_64, START + 0x2000));
+        assert_eq!((moved[4].code(), moved[4].near_branch64()), (Code::Jmp_rel32_64, START + 15));
+    }
+    
+    #[test]
+    fn unmovable_entries() {
+        assert!(entry_instructions(&METHOD[..5], START, 6).is_err()); //too short
+        assert!(entry_instructions(&[0xEB, 0x04, 0x90, 0x90, 0x90, 0x90, 0x90, 0x90, 0xC3], START, 6).is_err()); //jumps first
+        assert!(entry_instructions(&[0x90, 0x90, 0x90, 0x90, 0x90, 0x90, 0x74, 0xFB, 0xC3], START, 6).is_err()); //jumps back into the hook
+        assert!(entry_instructions(&[0x90, 0x90, 0x90, 0x90, 0x90, 0x90, 0x74, 0xF8, 0xC3], START, 6).is_ok()); //jumps back to the start, which the hook keeps
+    }
+}
@@ -0,0 +1,152 @@
+use std::{fs::read_to_string, path::Path};
+
+use fxhash::FxHashSet;
+use serde::{Deserialize, Serialize};
+
+use crate::{data::{Unlockable, ZombieType}, util::CommonError};
+
+const PLAN_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Plan { //hand picked parts of a seed, the rest is randomised around them with restrictions
+    pub version: u32,
+    #[serde(default)]
+    pub slots:   Vec<PlannedSlot>,
+    #[serde(default)]
+    pub levels:  Vec<PlannedLevel>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PlannedSlot {
+    pub slot:   u8, //counts from 1 in the order levels are played
+    #[serde(default)]
+    pub level:  Option<u8>,
+    #[serde(default)]
+    pub unlock: Option<Unlockable>, //the plant the level in this slot unlocks, slot 1 picks the first plant
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PlannedLevel {
+    pub level:   u8,
+    #[serde(default)]
+    pub zombies: Option<Vec<(ZombieType, u32)>>, //replaces the level's zombies and weights, they aren't lowered to make the level easier
+    #[serde(default)]
+    pub plants:  Vec<PlannedPlant>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PlannedPlant { //multipliers like the spoiler log shows them, from 0.5 to 2, lower is stronger
+    pub plant:    Unlockable,
+    #[serde(default)]
+    pub firerate: Option<f32>,
+    #[serde(default)]
+    pub cooldown: Option<f32>,
+    #[serde(default)]
+    pub cost:     Option<f32>,
+}
+
+impl Plan {
+    pub fn parse(text: &str) -> Result<Self, CommonError> {
+        Self::parse_inner(text).map_err(|err| CommonError::critical(&format!("Invalid plan: {err}")))
+    }
+    
+    fn parse_inner(text: &str) -> Result<Self, String> {
+        let plan: Plan = ron::from_str(text).map_err(|err| err.to_string())?;
+        plan.check()?;
+        Ok(plan)
+    }
+    
+    pub fn load_file<T: AsRef<Path>>(path: T) -> Result<Self, CommonError> {
+        let text = match read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) => return Err(CommonError::critical(&format!("Failed to read {}: {err}", path.as_ref().display()))),
+        };
+        Self::parse_inner(&text).map_err(|err| CommonError::critical(&format!("{} is invalid: {err}", path.as_ref().display())))
+    }
+    
+    pub fn to_ron(&self) -> String {
+        ron::to_string(self).unwrap()
+    }
+    
+    pub fn slot(&self, slot: u8) -> Option<&PlannedSlot> {
+        self.slots.iter().find(|planned| planned.slot == slot)
+    }
+    
+    fn check(&self) -> Result<(), String> { //only what can be checked without the game's data, generating checks the rest
+        if self.version != PLAN_VERSION {
+            return Err(format!("Plan is version {}, but this randomiser only supports version {PLAN_VERSION}", self.version));
+        }
+        
+        let mut slots:   FxHashSet<u8> = FxHashSet::default();
+        let mut levels:  FxHashSet<u8> = FxHashSet::default();
+        let mut unlocks: FxHashSet<Unlockable> = FxHashSet::default();
+        for planned in &self.slots {
+            let slot = planned.slot;
+            if !(1..=45).contains(&slot) {
+                return Err(format!("Slot {slot} doesn't exist, slots go from 1 to 45"));
+            }
+            if !slots.insert(slot) {
+                return Err(format!("Slot {slot} is planned more than once"));
+            }
+            if planned.level.is_none() && planned.unlock.is_none() {
+                return Err(format!("Slot {slot} needs a level or an unlock"));
+            }
+            if let Some(level) = planned.level {
+                if !(1..=45).contains(&level) {
+                    return Err(format!("Slot {slot} has level {level}, but levels go from 1 to 45"));
+                }
+                if (slot == 1) != (level == 1) {
+                    return Err(format!("Slot {slot} has level {level}, but level 1 is always played first"));
+                }
+                if !levels.insert(level) {
+                    return Err(format!("Level {level} is in more than one slot"));
+                }
+            }
+            if let Some(plant) = planned.unlock {
+                if matches!(plant, Unlockable::Peashooter | Unlockable::SunFlower) {
+                    return Err(format!("Slot {slot} unlocks {plant:?}, but it is always unlocked"));
+                }
+                if !unlocks.insert(plant) {
+                    return Err(format!("{plant:?} is unlocked by more than one slot"));
+                }
+            }
+        }
+        
+        let mut levels: FxHashSet<u8> = FxHashSet::default();
+        for planned in &self.levels {
+            let level = planned.level;
+            if !(2..=45).contains(&level) {
+                return Err(format!("Level {level} can't be changed, only levels 2 to 45 can"));
+            }
+            if !levels.insert(level) {
+                return Err(format!("Level {level} is listed more than once in levels"));
+            }
+            if let Some(zombies) = &planned.zombies {
+                if zombies.is_empty() {
+                    return Err(format!("Level {level} has no zombies"));
+                }
+                let mut seen: FxHashSet<ZombieType> = FxHashSet::default();
+                for (zombie, weight) in zombies {
+                    if *weight == 0 {
+                        return Err(format!("{zombie:?} has a weight of 0 in level {level}, leave it out instead"));
+                    }
+                    if !seen.insert(*zombie) {
+                        return Err(format!("{zombie:?} is listed more than once in level {level}"));
+                    }
+                }
+            }
+            let mut seen: FxHashSet<Unlockable> = FxHashSet::default();
+            for plant in &planned.plants {
+                if !seen.insert(plant.plant) {
+                    return Err(format!("{:?} is listed more than once in level {level}", plant.plant));
+                }
+                for (what, mul) in [("firerate", plant.firerate), ("cooldown", plant.cooldown), ("cost", plant.cost)] {
+                    if mul.is_some_and(|mul| !(0.5..=2.).contains(&mul)) {
+                        return Err(format!("{:?} has a {what} of {} in level {level}, but it needs to be from 0.5 to 2", plant.plant, mul.unwrap()));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+use fxhash::FxHashSet;
+use serde::{Deserialize, Serialize};
+
+use crate::{data::Unlockable, logic::{GenerationError, RandomisationData}};
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub struct PlantOptions { //only used with restrictions
+    pub first_plant:  Option<Unlockable>, //None picks a random first plant
+    pub early_plants: Vec<Unlockable>,    //unlocked by one of the first early_levels levels
+    pub early_levels: u8,
+    pub excluded:     Vec<Unlockable>,    //never unlocked
+    pub weighted:     bool,               //picks the remaining plants by usefulness instead of uniformly
+}
+
+impl Default for PlantOptions {
+    fn default() -> Self {
+        Self {
+            first_plant:  None,
+            early_plants: Vec::new(),
+            early_levels: 10,
+            excluded:     Vec::new(),
+            weighted:     false,
+        }
+    }
+}
+
+impl PlantOptions {
+    pub fn excluded_set(&self) -> FxHashSet<Unlockable> { //includes upgrades of excluded plants, since they could never be unlocked
+        let mut ret: FxHashSet<Unlockable> = self.excluded.iter().copied().collect();
+        for plant in (0..=Unlockable::CobCannon as u8).filter_map(Unlockable::from_u8) {
+            if ret.contains(&RandomisationData::upgrade_to_plant(plant)) {
+                ret.insert(plant);
+            }
+        }
+        ret
+    }
+    
+    pub fn early_plants(&self, first_plant: Unlockable) -> Result<Vec<Unlockable>, GenerationError> { //adds the plants needed for early upgrades
+        let mut ret: Vec<Unlockable> = Vec::with_capacity(self.early_plants.len());
+        for plant in &self.early_plants {
+            for plant in [RandomisationData::upgrade_to_plant(*plant), *plant] {
+                if !matches!(plant, Unlockable::Peashooter | Unlockable::SunFlower) && plant != first_plant && !ret.contains(&plant) {
+                    ret.push(plant);
+                }
+            }
+        }
+        if ret.len() >= self.early_levels as usize {
+            return Err(GenerationError::TooManyEarlyPlants(ret.len(), self.early_levels));
+        }
+        Ok(ret)
+    }
+    
+    pub fn check(&self, excluded: &FxHashSet<Unlockable>) -> Result<(), GenerationError> {
+        for plant in [Unlockable::Peashooter, Unlockable::SunFlower].iter().chain(self.first_plant.iter()).chain(self.early_plants.iter()) {
+            if excluded.contains(plant) {
+                return Err(GenerationError::ExcludedPlant(*plant));
+            }
+        }
+        if let Some(plant) = self.first_plant {
+            if matches!(plant, Unlockable::Peashooter | Unlockable::SunFlower) || RandomisationData::upgrade_to_plant(plant) != plant {
+                return Err(GenerationError::InvalidFirstPlant(plant));
+            }
+        }
+        Ok(())
+    }
+}
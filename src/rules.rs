@@ -0,0 +1,207 @@
+use std::{collections::HashMap, fs::read_to_string, path::{Path, PathBuf}, sync::OnceLock};
+
+use fxhash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::config_dir, data::{Unlockable, ZombieFlags, ZombieType, PLANT_DATA}, logic::{Problem, SolutionEntry, Solutions, PROBLEM_FLAGS}, util::{hash_str, CommonError}};
+
+pub static RULES: OnceLock<Rules> = OnceLock::new();
+
+pub const DEFAULT_RULES: &str = include_str!("rules.ron");
+const RULES_VERSION: u32 = 1;
+
+#[derive(Deserialize, Serialize)]
+struct RulesFile {
+    version:             u32,
+    solutions_1:         Vec<(Problem, Vec<Vec<Unlockable>>)>,
+    solutions_2:         Vec<(Problem, Vec<Vec<Unlockable>>)>,
+    solutions_3:         Vec<(Problem, Vec<Vec<Unlockable>>)>,
+    flag_solutions:      Vec<(String, Vec<SolutionRule>)>,
+    zombie_solutions:    Vec<(ZombieType, Vec<SolutionRule>)>,
+    firepower_solutions: Vec<(u8, Vec<SolutionRule>)>,
+    #[serde(default)]
+    plant_usefulness:    Vec<(Unlockable, f32)>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+struct SolutionRule {
+    plants: Vec<Unlockable>,
+    uses:   Option<u8>,
+    negate: bool,
+    night:  bool,
+    weight: f32,
+}
+
+impl Default for SolutionRule {
+    fn default() -> Self {
+        Self {
+            plants: Vec::new(),
+            uses:   Some(1),
+            negate: false,
+            night:  false,
+            weight: 1.,
+        }
+    }
+}
+
+pub struct Rules {
+    pub solutions:           [FxHashMap<Problem, Solutions>; 3], //in the order they are assigned to the unlock order
+    pub all_solutions:       FxHashMap<Problem, Solutions>,
+    pub flag_solutions:      FxHashMap<ZombieFlags, Box<[SolutionEntry]>>,
+    pub zombie_solutions:    FxHashMap<ZombieType, Box<[SolutionEntry]>>,
+    pub firepower_solutions: FxHashMap<u8, Box<[SolutionEntry]>>,
+    pub plant_usefulness:    [f32; 41], //indexed by Unlockable
+    pub hash:                u32, //of the parsed rules, so formatting and comments don't change it, settings codes only work with the same rules
+}
+
+fn convert_entries(what: &str, rules: Vec<SolutionRule>) -> Result<Box<[SolutionEntry]>, String> {
+    let mut ret = Vec::with_capacity(rules.len());
+    for rule in rules {
+        if rule.plants.is_empty() || rule.plants.len() > 4 {
+            return Err(format!("A solution for {what} has {} plants, but it needs 1 to 4", rule.plants.len()));
+        }
+        if !(rule.weight.is_finite() && rule.weight > 0.) {
+            return Err(format!("A solution for {what} has a weight of {}, but it needs to be positive", rule.weight));
+        }
+        if rule.uses == Some(0) {
+            return Err(format!("A solution for {what} has 0 uses, use None for unlimited uses"));
+        }
+        
+        let mut entry = SolutionEntry {
+            uses:   rule.uses,
+            negate: rule.negate,
+            night:  rule.night,
+            weight: rule.weight,
+            plants: rule.plants.into_iter().collect(),
+        };
+        entry.plants.sort_unstable_by_key(|x| *x as isize);
+        ret.push(entry);
+    }
+    Ok(ret.into_boxed_slice())
+}
+
+impl Rules {
+    pub fn parse(text: &str) -> Result<Self, CommonError> {
+        Self::parse_inner(text).map_err(|err| CommonError::critical(&format!("Invalid rules: {err}")))
+    }
+    
+    fn parse_inner(text: &str) -> Result<Self, String> {
+        let file: RulesFile = ron::from_str(text).map_err(|err| err.to_string())?;
+        if file.version != RULES_VERSION {
+            return Err(format!("Rules are version {}, but this randomiser only supports version {RULES_VERSION}", file.version));
+        }
+        let hash = hash_str(&ron::to_string(&file).map_err(|err| err.to_string())?) as u32;
+        
+        let mut solutions: [FxHashMap<Problem, Solutions>; 3] = Default::default();
+        let mut all_solutions: FxHashMap<Problem, Solutions> = HashMap::default();
+        for (tier, problems) in [file.solutions_1, file.solutions_2, file.solutions_3].into_iter().enumerate() {
+            for (problem, problem_solutions) in problems {
+                if problem_solutions.is_empty() {
+                    return Err(format!("{problem:?} has no solutions"));
+                }
+                if problem_solutions.iter().any(|solution| solution.is_empty()) {
+                    return Err(format!("{problem:?} has a solution with no plants"));
+                }
+                let problem_solutions: Solutions = problem_solutions.into_iter().map(|solution| solution.into_boxed_slice()).collect();
+                if all_solutions.insert(problem, problem_solutions.clone()).is_some() {
+                    return Err(format!("{problem:?} is listed more than once"));
+                }
+                solutions[tier].insert(problem, problem_solutions);
+            }
+        }
+        for problem in [Problem::Water1, Problem::Water2, Problem::Water34, Problem::Roof] {
+            if !all_solutions.contains_key(&problem) {
+                return Err(format!("{problem:?} is missing, it is needed to check levels"));
+            }
+        }
+        
+        let mut flag_solutions: FxHashMap<ZombieFlags, Box<[SolutionEntry]>> = HashMap::default();
+        for (name, rules) in file.flag_solutions {
+            let Some(flag) = ZombieFlags::from_name(&name) else {
+                return Err(format!("{name} is not a zombie flag"));
+            };
+            if flag_solutions.insert(flag, convert_entries(&name, rules)?).is_some() {
+                return Err(format!("{name} is listed more than once"));
+            }
+        }
+        for (name, flag) in PROBLEM_FLAGS.iter_names() {
+            if !flag_solutions.contains_key(&flag) {
+                return Err(format!("{name} is missing from flag_solutions"));
+            }
+        }
+        
+        let mut zombie_solutions: FxHashMap<ZombieType, Box<[SolutionEntry]>> = HashMap::default();
+        for (zombie, rules) in file.zombie_solutions {
+            if zombie_solutions.insert(zombie, convert_entries(&format!("{zombie:?}"), rules)?).is_some() {
+                return Err(format!("{zombie:?} is listed more than once"));
+            }
+        }
+        
+        let mut firepower_solutions: FxHashMap<u8, Box<[SolutionEntry]>> = HashMap::default();
+        for (flags, rules) in file.firepower_solutions {
+            if !(1..=4).contains(&flags) {
+                return Err(format!("Levels can't have {flags} flags, only 1 to 4"));
+            }
+            if firepower_solutions.insert(flags, convert_entries(&format!("{flags} flag levels"), rules)?).is_some() {
+                return Err(format!("{flags} flag levels are listed more than once"));
+            }
+        }
+        for flags in 1..=4 {
+            if !firepower_solutions.contains_key(&flags) {
+                return Err(format!("{flags} flag levels are missing from firepower_solutions"));
+            }
+        }
+        
+        let mut plant_usefulness = PLANT_DATA.map(|data| data.usefulness);
+        let mut overridden = [false; 41];
+        for (plant, usefulness) in file.plant_usefulness {
+            if !(usefulness.is_finite() && usefulness > 0.) {
+                return Err(format!("{plant:?} has a usefulness of {usefulness}, but it needs to be positive"));
+            }
+            if overridden[plant as usize] {
+                return Err(format!("{plant:?} is listed more than once in plant_usefulness"));
+            }
+            overridden[plant as usize] = true;
+            plant_usefulness[plant as usize] = usefulness;
+        }
+        
+        Ok(Self {
+            solutions,
+            all_solutions,
+            flag_solutions,
+            zombie_solutions,
+            firepower_solutions,
+            plant_usefulness,
+            hash,
+        })
+    }
+    
+    pub fn load_file<T: AsRef<Path>>(path: T) -> Result<Self, CommonError> {
+        let text = match read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) => return Err(CommonError::critical(&format!("Failed to read {}: {err}", path.as_ref().display()))),
+        };
+        Self::parse_inner(&text).map_err(|err| CommonError::critical(&format!("{} is invalid: {err}", path.as_ref().display())))
+    }
+}
+
+pub fn rules_path() -> Option<PathBuf> {
+    Some(config_dir()?.join("rules.ron"))
+}
+
+pub fn built_in_rules_hash() -> u32 {
+    Rules::parse(DEFAULT_RULES).unwrap().hash
+}
+
+pub fn init_rules() -> Result<(), CommonError> { //uses rules.ron from the config directory if there is one
+    let rules = match rules_path() {
+        Some(path) if path.exists() => {
+            println!("Using rules from {}", path.display());
+            Rules::load_file(path)?
+        }
+        _ => Rules::parse(DEFAULT_RULES)?,
+    };
+    RULES.get_or_init(|| rules);
+    Ok(())
+}
@@ -0,0 +1,443 @@
+use fxhash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{data::Unlockable, difficulty::Difficulty, logic::{GenerationError, GenerationOptions, RandomisationData}, plan::Plan, plant_options::PlantOptions, rules::RULES, util::{hash_str, CommonError}};
+
+const FORMAT_VERSION: u8 = 1;
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+type Rerolls = Vec<(u8, u8)>;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Cfg {
+    pub firerates_enabled: bool,
+    pub costs_enabled:     bool,
+    pub cooldowns_enabled: bool,
+    pub spawns_enabled:    bool,
+    pub tweaks_enabled:    bool,
+    pub restrictions:      bool,
+    pub sounds:            bool,
+    pub conveyors_enabled: bool,
+    pub flag_counts_enabled: bool,
+    pub scenes_enabled: bool,
+    pub zombie_stats_enabled: bool,
+    pub damage_enabled: bool,
+    pub sun_enabled: bool,
+    pub fusions_enabled: bool,
+    pub progressive_weights_enabled: bool,
+    pub seed:            String,
+    pub sound_chance:       f32,
+    pub difficulty:  Difficulty,
+    pub plant_options: PlantOptions,
+    pub rerolls: Rerolls, //slot and how many times it was rerolled, sorted by slot
+    pub plan:    Option<Plan>,
+}
+
+impl Default for Cfg {
+    fn default() -> Self {
+        Self {
+            firerates_enabled: true,
+            costs_enabled:     true,
+            cooldowns_enabled: true,
+            spawns_enabled:    true,
+            tweaks_enabled:    true,
+            restrictions:      true,
+            sounds:           false,
+            conveyors_enabled: false,
+            flag_counts_enabled: false,
+            scenes_enabled: false,
+            zombie_stats_enabled: false,
+            damage_enabled: false,
+            sun_enabled: false,
+            fusions_enabled: false,
+            progressive_weights_enabled: false,
+            seed:     String::new(),
+            sound_chance: 0.2,
+            difficulty: Difficulty::Standard,
+            plant_options: PlantOptions::default(),
+            rerolls: Vec::new(),
+            plan: None,
+        }
+    }
+}
+
+fn randomiser_version() -> (u8, u8) {
+    (
+        env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap(),
+        env!("CARGO_PKG_VERSION_MINOR").parse().unwrap(),
+    )
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0x5Au8, |acc, byte| acc.rotate_left(3) ^ byte)
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut ret = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let mut buf = [0u8; 3];
+        buf[..chunk.len()].copy_from_slice(chunk);
+        let val = (buf[0] as u32) << 16 | (buf[1] as u32) << 8 | buf[2] as u32;
+        for i in 0..=chunk.len() {
+            ret.push(ALPHABET[(val >> (18 - i * 6) & 0x3F) as usize] as char);
+        }
+    }
+    ret
+}
+
+fn decode_base64(string: &str) -> Option<Vec<u8>> {
+    let mut ret = Vec::with_capacity(string.len() * 3 / 4);
+    for chunk in string.as_bytes().chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let mut val = 0u32;
+        for (i, char) in chunk.iter().enumerate() {
+            let digit = ALPHABET.iter().position(|c| c == char)? as u32;
+            val |= digit << (18 - i * 6);
+        }
+        for i in 0..chunk.len() - 1 {
+            ret.push((val >> (16 - i * 8)) as u8);
+        }
+    }
+    Some(ret)
+}
+
+fn decode_plant_options(bytes: &[u8]) -> Result<(PlantOptions, &[u8]), CommonError> {
+    let too_short = || CommonError::inconvenience("Settings code is too short");
+    let unknown_plant = || CommonError::inconvenience("Settings code has an unknown plant");
+    let [first_plant, early_levels, bytes @ ..] = bytes else {
+        return Err(too_short());
+    };
+    let first_plant = match first_plant {
+        0xFF => None,
+        plant => Some(Unlockable::from_u8(*plant).ok_or_else(unknown_plant)?),
+    };
+    
+    let mut lists = [Vec::new(), Vec::new()];
+    let mut bytes = bytes;
+    for list in lists.iter_mut() {
+        let Some((len, rest)) = bytes.split_first() else {
+            return Err(too_short());
+        };
+        if rest.len() < *len as usize {
+            return Err(too_short());
+        }
+        let (plants, rest) = rest.split_at(*len as usize);
+        for plant in plants {
+            list.push(Unlockable::from_u8(*plant).ok_or_else(unknown_plant)?);
+        }
+        bytes = rest;
+    }
+    let [early_plants, excluded] = lists;
+    
+    Ok((PlantOptions {
+        first_plant,
+        early_plants,
+        early_levels: *early_levels,
+        excluded,
+        weighted: false, //stored in the flags
+    }, bytes))
+}
+
+fn decode_rerolls(bytes: &[u8]) -> Result<(Rerolls, &[u8]), CommonError> {
+    let Some((len, bytes)) = bytes.split_first() else {
+        return Err(CommonError::inconvenience("Settings code is too short"));
+    };
+    if bytes.len() < *len as usize * 2 {
+        return Err(CommonError::inconvenience("Settings code is too short"));
+    }
+    let (rerolls, bytes) = bytes.split_at(*len as usize * 2);
+    Ok((rerolls.chunks_exact(2).map(|pair| (pair[0], pair[1])).collect(), bytes))
+}
+
+fn decode_plan(bytes: &[u8]) -> Result<(Option<Plan>, &[u8]), CommonError> {
+    if bytes.len() < 2 {
+        return Err(CommonError::inconvenience("Settings code is too short"));
+    }
+    let (len, bytes) = bytes.split_at(2);
+    let len = u16::from_le_bytes([len[0], len[1]]) as usize;
+    if bytes.len() < len {
+        return Err(CommonError::inconvenience("Settings code is too short"));
+    }
+    let (plan, bytes) = bytes.split_at(len);
+    if plan.is_empty() {
+        return Ok((None, bytes));
+    }
+    let Some(plan) = std::str::from_utf8(plan).ok().and_then(|text| Plan::parse(text).ok()) else {
+        return Err(CommonError::inconvenience("Settings code has an invalid plan"));
+    };
+    Ok((Some(plan), bytes))
+}
+
+impl Cfg {
+    pub fn randomise(&self, enum_variants: &FxHashMap<String, u64>, fuse_data: &FxHashMap<u32,[u32;2]>) -> Result<RandomisationData, GenerationError> {
+        if self.restrictions {
+            RandomisationData::restrictions(hash_str(&self.seed), &self.generation_options(), enum_variants, fuse_data)
+        } else {
+            RandomisationData::no_restrictions(hash_str(&self.seed), &self.generation_options(), enum_variants, fuse_data)
+        }
+    }
+    
+    pub fn generation_options(&self) -> GenerationOptions {
+        GenerationOptions {
+            difficulty:    self.difficulty,
+            plant_options: self.plant_options.clone(),
+            conveyors:     self.conveyors_enabled,
+            flag_counts:   self.flag_counts_enabled,
+            scenes:        self.scenes_enabled,
+            zombie_stats:  self.zombie_stats_enabled,
+            plant_damage:  self.damage_enabled,
+            sun_production: self.sun_enabled,
+            fusions:       self.fusions_enabled,
+            progressive_weights: self.progressive_weights_enabled,
+            rerolls:       if self.restrictions {self.rerolls.clone()} else {Vec::new()},
+            plan:          if self.restrictions {self.plan.clone()} else {None},
+            threads:       0,
+        }
+    }
+    
+    pub fn reroll(&mut self, slot: u8) {
+        match self.rerolls.binary_search_by_key(&slot, |(slot, _)| *slot) {
+            Ok(idx) => self.rerolls[idx].1 = self.rerolls[idx].1.saturating_add(1),
+            Err(idx) => self.rerolls.insert(idx, (slot, 1)),
+        }
+    }
+    
+    fn flags(&self) -> u16 {
+        [
+            self.firerates_enabled,
+            self.costs_enabled,
+            self.cooldowns_enabled,
+            self.spawns_enabled,
+            self.tweaks_enabled,
+            self.restrictions,
+            self.sounds,
+            self.conveyors_enabled,
+            self.flag_counts_enabled,
+            self.scenes_enabled,
+            self.zombie_stats_enabled,
+            self.damage_enabled,
+            self.sun_enabled,
+            self.fusions_enabled,
+            self.plant_options.weighted,
+            self.progressive_weights_enabled,
+        ].iter().enumerate().fold(0, |acc, (i, flag)| acc | (*flag as u16) << i)
+    }
+    
+    pub fn to_settings_string(&self) -> Result<String, CommonError> {
+        let (major, minor) = randomiser_version();
+        let [flags_low, flags_high] = self.flags().to_le_bytes();
+        let mut bytes = vec![FORMAT_VERSION, major, minor, flags_low, flags_high, self.difficulty as u8];
+        let options = &self.plant_options;
+        bytes.push(options.first_plant.map_or(0xFF, |plant| plant as u8));
+        bytes.push(options.early_levels);
+        for plants in [&options.early_plants, &options.excluded] {
+            bytes.push(plants.len() as u8);
+            bytes.extend(plants.iter().map(|plant| *plant as u8));
+        }
+        bytes.push(self.rerolls.len() as u8);
+        for (slot, rerolls) in &self.rerolls {
+            bytes.extend([*slot, *rerolls]);
+        }
+        let plan = self.plan.as_ref().map(Plan::to_ron).unwrap_or_default();
+        let Ok(plan_len) = u16::try_from(plan.len()) else {
+            return Err(CommonError::inconvenience("The plan is too big to fit in a settings code"));
+        };
+        bytes.extend_from_slice(&plan_len.to_le_bytes());
+        bytes.extend_from_slice(plan.as_bytes());
+        bytes.extend_from_slice(&RULES.get().unwrap().hash.to_le_bytes());
+        bytes.extend_from_slice(&self.sound_chance.to_le_bytes());
+        bytes.extend_from_slice(self.seed.as_bytes());
+        bytes.push(checksum(&bytes));
+        
+        Ok(encode_base64(&bytes))
+    }
+    
+    pub fn from_settings_string(string: &str) -> Result<Self, CommonError> {
+        let Some(bytes) = decode_base64(string.trim()) else {
+            return Err(CommonError::inconvenience("Settings code is malformed, make sure it was copied fully"));
+        };
+        let Some((check, bytes)) = bytes.split_last() else {
+            return Err(CommonError::inconvenience("Settings code is empty"));
+        };
+        if checksum(bytes) != *check {
+            return Err(CommonError::inconvenience("Settings code is corrupted, make sure it was copied fully"));
+        }
+        if bytes.len() < 3 {
+            return Err(CommonError::inconvenience("Settings code is too short"));
+        }
+        if bytes[0] > FORMAT_VERSION {
+            return Err(CommonError::inconvenience("Settings code is from a newer version of the randomiser"));
+        }
+        
+        let (major, minor) = randomiser_version();
+        if (bytes[1], bytes[2]) != (major, minor) {
+            return Err(CommonError::inconvenience(&format!(
+                "Settings code is for randomiser v{}.{}, but this is v{major}.{minor}, so the same seed would generate a different game",
+                bytes[1], bytes[2],
+            )));
+        }
+        if bytes[0] != FORMAT_VERSION {
+            return Err(CommonError::inconvenience("Settings code has an unknown format"));
+        }
+        let [_, _, _, flags_low, flags_high, difficulty, bytes @ ..] = bytes else {
+            return Err(CommonError::inconvenience("Settings code is too short"));
+        };
+        
+        let flags = u16::from_le_bytes([*flags_low, *flags_high]);
+        let flag = |i: u8| flags & (1 << i) != 0;
+        let Some(difficulty) = Difficulty::from_u8(*difficulty) else {
+            return Err(CommonError::inconvenience("Settings code has an unknown difficulty"));
+        };
+        let (plant_options, bytes) = decode_plant_options(bytes)?;
+        let (rerolls, bytes) = decode_rerolls(bytes)?;
+        let (plan, bytes) = decode_plan(bytes)?;
+        if bytes.len() < 4 {
+            return Err(CommonError::inconvenience("Settings code is too short"));
+        }
+        let (rules_hash, bytes) = (u32::from_le_bytes(bytes[..4].try_into().unwrap()), &bytes[4..]);
+        if rules_hash != RULES.get().unwrap().hash {
+            return Err(CommonError::inconvenience(&format!(
+                "Settings code is for rules {rules_hash:08x}, but these are {:08x}, so the same seed would generate a different game. Custom rules are read from rules.ron in the config directory",
+                RULES.get().unwrap().hash,
+            )));
+        }
+        if bytes.len() < 4 {
+            return Err(CommonError::inconvenience("Settings code is too short"));
+        }
+        
+        let Ok(seed) = String::from_utf8(bytes[4..].to_vec()) else {
+            return Err(CommonError::inconvenience("Settings code has an invalid seed"));
+        };
+        
+        Ok(Self {
+            firerates_enabled: flag(0),
+            costs_enabled:     flag(1),
+            cooldowns_enabled: flag(2),
+            spawns_enabled:    flag(3),
+            tweaks_enabled:    flag(4),
+            restrictions:      flag(5),
+            sounds:            flag(6),
+            conveyors_enabled: flag(7),
+            flag_counts_enabled: flag(8),
+            scenes_enabled: flag(9),
+            zombie_stats_enabled: flag(10),
+            damage_enabled: flag(11),
+            sun_enabled: flag(12),
+            fusions_enabled: flag(13),
+            progressive_weights_enabled: flag(15),
+            seed,
+            sound_chance: f32::from_le_bytes(bytes[0..4].try_into().unwrap()).clamp(0.0, 1.0),
+            difficulty,
+            plant_options: PlantOptions {
+                weighted: flag(14),
+                ..plant_options
+            },
+            rerolls,
+            plan,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{data::{Unlockable, ZombieType}, difficulty::Difficulty, plan::{Plan, PlannedLevel}, plant_options::PlantOptions, rules::{Rules, DEFAULT_RULES, RULES}};
+    use super::{checksum, decode_base64, encode_base64, Cfg};
+    
+    fn setup() {
+        RULES.get_or_init(|| Rules::parse(DEFAULT_RULES).unwrap());
+    }
+    
+    fn custom_cfg() -> Cfg { //every option away from its default
+        Cfg {
+            firerates_enabled: false,
+            costs_enabled:     true,
+            cooldowns_enabled: false,
+            spawns_enabled:    true,
+            tweaks_enabled:    false,
+            restrictions:      true,
+            sounds:            true,
+            conveyors_enabled: true,
+            flag_counts_enabled: false,
+            scenes_enabled: true,
+            zombie_stats_enabled: true,
+            damage_enabled: false,
+            sun_enabled: true,
+            fusions_enabled: false,
+            progressive_weights_enabled: true,
+            seed:     "fusion ✿ 14159".to_owned(),
+            sound_chance: 0.35,
+            difficulty: Difficulty::Chaos,
+            plant_options: PlantOptions {
+                first_plant:  Some(Unlockable::Squash),
+                early_plants: vec![Unlockable::LilyPad, Unlockable::Pot],
+                early_levels: 12,
+                excluded:     vec![Unlockable::Chomper],
+                weighted:     true,
+            },
+            rerolls: vec![(4, 1), (17, 3)],
+            plan:    Some(Plan::parse("(version: 1, slots: [(slot: 3, level: Some(10), unlock: Some(Squash))])").unwrap()),
+        }
+    }
+    
+    fn reencode(string: &str, edit: impl FnOnce(&mut Vec<u8>)) -> String { //changes the bytes of a settings code but keeps its checksum valid
+        let mut bytes = decode_base64(string).unwrap();
+        bytes.pop();
+        edit(&mut bytes);
+        bytes.push(checksum(&bytes));
+        encode_base64(&bytes)
+    }
+    
+    #[test]
+    fn round_trips() {
+        setup();
+        
+        for cfg in [Cfg::default(), custom_cfg()] {
+            let string = cfg.to_settings_string().unwrap();
+            let decoded = Cfg::from_settings_string(&string).unwrap();
+            assert_eq!(decoded.flags(), cfg.flags());
+            assert_eq!(decoded.seed, cfg.seed);
+            assert_eq!(decoded.sound_chance, cfg.sound_chance);
+            assert_eq!(decoded.difficulty, cfg.difficulty);
+            assert_eq!(decoded.plant_options, cfg.plant_options);
+            assert_eq!(decoded.rerolls, cfg.rerolls);
+            assert_eq!(decoded.plan, cfg.plan);
+            assert_eq!(decoded.to_settings_string().unwrap(), string);
+        }
+        for base64 in [&b""[..], b"a", b"ab", b"abc", b"abcd"] {
+            assert_eq!(decode_base64(&encode_base64(base64)).unwrap(), base64);
+        }
+    }
+    
+    #[test]
+    fn rejected_codes() {
+        setup();
+        let string = custom_cfg().to_settings_string().unwrap();
+        
+        assert!(Cfg::from_settings_string("").is_err());
+        assert!(Cfg::from_settings_string("not a code!").is_err());
+        assert!(Cfg::from_settings_string(&string[..string.len() - 4]).is_err()); //cut short
+        let mut corrupted = string.clone().into_bytes();
+        corrupted[10] = if corrupted[10] == b'A' {b'B'} else {b'A'};
+        assert!(Cfg::from_settings_string(std::str::from_utf8(&corrupted).unwrap()).is_err());
+        
+        assert!(Cfg::from_settings_string(&reencode(&string, |bytes| bytes[0] += 1)).is_err()); //a newer format
+        assert!(Cfg::from_settings_string(&reencode(&string, |bytes| bytes[2] = bytes[2].wrapping_add(1))).is_err()); //another randomiser version
+        assert!(Cfg::from_settings_string(&reencode(&string, |bytes| bytes[5] = 0xFF)).is_err()); //unknown difficulty
+        assert!(Cfg::from_settings_string(&reencode(&string, |bytes| bytes.truncate(12))).is_err());
+        
+        let huge_plan = Plan {
+            version: 1,
+            slots:   Vec::new(),
+            levels:  vec![PlannedLevel {
+                level:   5,
+                zombies: Some(vec![(ZombieType::NormalZombie, 4000); 5000]),
+                plants:  Vec::new(),
+            }],
+        };
+        assert!(huge_plan.to_ron().len() > u16::MAX as usize);
+        assert!(Cfg {plan: Some(huge_plan), ..custom_cfg()}.to_settings_string().is_err());
+    }
+}
@@ -0,0 +1,243 @@
+use std::{error::Error, fs::OpenOptions, io::Write, mem::transmute, path::Path};
+
+use fxhash::FxHashMap;
+use serde::Serialize;
+
+use crate::{data::{level_label_in_scene, Unlockable, ZOMBIE_DATA}, format_to, logic::{mul_from_u8, RandomisationData, ZOMBIE_STAT_STRIDE}, rules::{built_in_rules_hash, RULES}};
+
+#[derive(Serialize)]
+pub struct Spoiler {
+    pub seed:    String,
+    pub rules:   String, //the hash of the rules used, which settings codes also store
+    pub fusions: Vec<FusionSpoiler>,
+    pub slots:   Vec<SlotSpoiler>,
+}
+
+#[derive(Serialize)]
+pub struct FusionSpoiler {
+    pub name:   String,
+    pub plants: [String; 2],
+}
+
+#[derive(Serialize)]
+pub struct SlotSpoiler {
+    pub slot:       usize,
+    pub level:      u8,
+    pub stage:      String,
+    pub unlocks:    Option<String>,
+    pub flags:      Option<u8>,
+    pub weight_scaling: Option<(f32, f32)>,
+    pub zombies:    Vec<ZombieSpoiler>,
+    pub plants:     Vec<PlantSpoiler>,
+    pub conveyor:   Vec<ConveyorSpoiler>,
+    pub sound_seed: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct ZombieSpoiler {
+    pub name:   &'static str,
+    pub weight: u32,
+    pub health: Option<f32>,
+    pub speed:  Option<f32>,
+    pub damage: Option<f32>,
+}
+
+#[derive(Serialize)]
+pub struct ConveyorSpoiler {
+    pub name:   String,
+    pub weight: u8,
+}
+
+#[derive(Serialize)]
+pub struct PlantSpoiler {
+    pub name:     String,
+    pub firerate: Option<f32>,
+    pub cooldown: Option<f32>,
+    pub cost:     Option<f32>,
+    pub damage:   Option<f32>,
+    pub sun:      Option<f32>,
+}
+
+impl Spoiler {
+    pub fn new(seed: &str, rand_data: &RandomisationData, enum_variants: &FxHashMap<String, u64>) -> Self {
+        let zombie_data = ZOMBIE_DATA.get().unwrap();
+        let (plant_map, _, _) = RandomisationData::get_plant_map_and_ids(enum_variants);
+        
+        let mut fusions = Vec::new();
+        if let Some(fuse_map) = &rand_data.fusions {
+            let plant_names: FxHashMap<u32, &str> = enum_variants.iter()
+                .filter(|(_, val)| **val as i64 >= 0)
+                .filter_map(|(name, val)| Some((*val as u32, name.strip_prefix("PlantType::")?)))
+                .collect();
+            let plant_name = |id: u32| plant_names.get(&id).map_or_else(|| id.to_string(), |name| name.to_string());
+            let mut recipes: Vec<(&u32, &[u32;2])> = fuse_map.iter().collect();
+            recipes.sort_unstable();
+            for (fusion, [plant_1, plant_2]) in recipes {
+                fusions.push(FusionSpoiler {
+                    name:   plant_name(*fusion),
+                    plants: [plant_name(*plant_1), plant_name(*plant_2)],
+                });
+            }
+        }
+        let mut slots = Vec::with_capacity(rand_data.level_order.len());
+        
+        for (slot, level) in rand_data.level_order.iter().enumerate() {
+            let mut zombies = Vec::new();
+            if let (Some(spawns), Some(weights)) = (&rand_data.spawns, &rand_data.weights) {
+                for (i, bytes) in weights[slot].chunks_exact(4).enumerate() {
+                    if spawns[slot][i >> 3] & (1 << (i & 7)) != 0 {
+                        let stat_mul = |stat: usize| -> Option<f32> {
+                            rand_data.zombie_stats.as_ref()?[slot].get(stat * ZOMBIE_STAT_STRIDE + i).copied().map(mul_from_u8)
+                        };
+                        zombies.push(ZombieSpoiler {
+                            name:   zombie_data[i].name,
+                            weight: u32::from_le_bytes(bytes.try_into().unwrap()),
+                            health: stat_mul(0),
+                            speed:  stat_mul(1),
+                            damage: stat_mul(2),
+                        });
+                    }
+                }
+            }
+            
+            let mut unlocks = None;
+            let mut plants  = Vec::new();
+            for i in 0..=Unlockable::CobCannon as u8 {
+                let plant: Unlockable = unsafe { transmute(i) };
+                let plant_slot = rand_data.plant_order[i as usize];
+                if plant_slot as usize == slot + 2 { //the plant is usable from the slot after the one that unlocks it
+                    unlocks = Some(format!("{plant:?}"));
+                }
+                if plant_slot as usize > slot + 1 {
+                    continue;
+                }
+                
+                let table_mul = |table: &Option<Vec<Vec<u8>>>, idx: Option<usize>| -> Option<f32> {
+                    table.as_ref()?[slot].get(idx?).copied().map(mul_from_u8)
+                };
+                let plant_idx = plant_map.get(&format!("{plant:?}")).map(|idx| *idx as usize);
+                plants.push(PlantSpoiler {
+                    name:     format!("{plant:?}"),
+                    firerate: table_mul(&rand_data.firerates, plant_idx),
+                    cooldown: table_mul(&rand_data.cooldowns, Some(i as usize)),
+                    cost:     table_mul(&rand_data.costs, Some(i as usize)),
+                    damage:   table_mul(&rand_data.plant_damage, plant_idx),
+                    sun:      table_mul(&rand_data.sun_production, plant_idx),
+                });
+            }
+            
+            let mut conveyor = Vec::new();
+            if let Some(conveyors) = &rand_data.conveyors {
+                for (i, weight) in conveyors[slot].iter().enumerate() {
+                    if let (Some(plant), 1..) = (Unlockable::from_u8(i as u8), weight) {
+                        conveyor.push(ConveyorSpoiler {
+                            name:   format!("{plant:?}"),
+                            weight: *weight,
+                        });
+                    }
+                }
+            }
+            
+            slots.push(SlotSpoiler {
+                slot: slot + 1,
+                level: *level,
+                stage: level_label_in_scene(*level as usize, rand_data.scene(*level as usize)),
+                unlocks,
+                flags: rand_data.flag_counts.as_ref().and_then(|_| rand_data.flag_count(*level as usize).ok()),
+                weight_scaling: rand_data.weight_scaling.as_ref().map(|scaling| scaling[slot]),
+                zombies,
+                plants,
+                conveyor,
+                sound_seed: rand_data.sound_seeds.as_ref().map(|seeds| seeds[slot]),
+            });
+        }
+        
+        Self {
+            seed: seed.to_owned(),
+            rules: format!("{:08x}", RULES.get().unwrap().hash),
+            fusions,
+            slots,
+        }
+    }
+    
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        
+        format_to!(out, "Seed: {}\n", self.seed);
+        format_to!(out, "Rules: {}{}\n", self.rules, if self.rules == format!("{:08x}", built_in_rules_hash()) {" (built in)"} else {""});
+        if !self.fusions.is_empty() {
+            format_to!(out, "\nFusions:\n");
+            for fusion in &self.fusions {
+                format_to!(out, "    {:<28} {} + {}\n", fusion.name, fusion.plants[0], fusion.plants[1]);
+            }
+        }
+        for slot in &self.slots {
+            format_to!(out, "\nSlot {}: {} (level {})\n", slot.slot, slot.stage, slot.level);
+            if let Some(plant) = &slot.unlocks {
+                format_to!(out, "    Unlocks: {plant}\n");
+            }
+            if let Some(flags) = slot.flags {
+                format_to!(out, "    Flags: {flags}\n");
+            }
+            if let Some((normal, elite)) = slot.weight_scaling {
+                format_to!(out, "    Weight scaling: x{normal:.2} (elites x{elite:.2})\n");
+            }
+            if !slot.zombies.is_empty() {
+                format_to!(out, "    Zombies:                      weight   health    speed   damage\n");
+                for zombie in &slot.zombies {
+                    format_to!(out, "        {:<24} {:>8}", zombie.name, zombie.weight);
+                    for mul in [zombie.health, zombie.speed, zombie.damage] {
+                        match mul {
+                            Some(mul) => format_to!(out, " {:>8}", format!("x{mul:.2}")),
+                            None      => format_to!(out, " {:>8}", "-"),
+                        }
+                    }
+                    out.push('\n');
+                }
+            }
+            format_to!(out, "    Plants:                  firerate cooldown     cost   damage      sun\n");
+            for plant in &slot.plants {
+                format_to!(out, "        {:<24}", plant.name);
+                for mul in [plant.firerate, plant.cooldown, plant.cost, plant.damage, plant.sun] {
+                    match mul {
+                        Some(mul) => format_to!(out, " {:>8}", format!("x{mul:.2}")),
+                        None      => format_to!(out, " {:>8}", "-"),
+                    }
+                }
+                out.push('\n');
+            }
+            if !slot.conveyor.is_empty() {
+                format_to!(out, "    Conveyor:\n");
+                for plant in &slot.conveyor {
+                    format_to!(out, "        {:<24} {}\n", plant.name, plant.weight);
+                }
+            }
+            if let Some(sound_seed) = slot.sound_seed {
+                format_to!(out, "    Sound seed: {sound_seed:016x}\n");
+            }
+        }
+        
+        out
+    }
+    
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+    
+    pub fn output<T: AsRef<Path>>(&self, out_dir: T) -> Result<(), Box<dyn Error>> {
+        for (name, contents) in [
+            ("spoiler.txt",  self.to_text()),
+            ("spoiler.json", self.to_json()),
+        ] {
+            let mut out_file = OpenOptions::new()
+                .create(true)
+                .truncate(true)
+                .write(true)
+                .open(out_dir.as_ref().join(name))?;
+            
+            out_file.write_all(contents.as_bytes())?;
+        }
+        
+        Ok(())
+    }
+}